@@ -18,9 +18,14 @@
 
 mod menus;
 
+pub mod applescript;
 pub mod helpers;
 pub mod password;
 
+pub use crate::app::menus::wallpaper::slideshow;
+
+use crate::app::menus::bluetooth::BluetoothData;
+use crate::app::menus::security::SecurityData;
 use crate::app::menus::sip::SIPData;
 use crate::app::menus::sound::SoundData;
 use crate::app::menus::wallpaper::WallpaperData;
@@ -39,6 +44,7 @@ enum Menu {
     Wallpaper,
     Sound,
     Sip,
+    Security,
     About,
 }
 #[derive(Default)]
@@ -48,6 +54,8 @@ pub struct MyApp {
     sound_data: SoundData,
     wifi_data: WifiData,
     sip_data: SIPData,
+    security_data: SecurityData,
+    bluetooth_data: BluetoothData,
 }
 
 impl eframe::App for MyApp {
@@ -80,10 +88,16 @@ impl eframe::App for MyApp {
             });
         });
 
+        // The volume worker polls in the background, so stop it however the menu was left
+        if !matches!(self.selected_menu, Menu::Sound) {
+            self.sound_data.stop_worker();
+        }
+
         match self.selected_menu {
             Menu::Main => menus::main_menu::main(self, ctx),
             Menu::Wallpaper => menus::wallpaper::main(self, ctx),
             Menu::Sip => menus::sip::main(self, ctx),
+            Menu::Security => menus::security::main(self, ctx),
             Menu::WiFi => menus::wifi::main(self, ctx),
             Menu::Bluetooth => menus::bluetooth::main(self, ctx),
             Menu::Sound => menus::sound::main(self, ctx),
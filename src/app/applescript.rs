@@ -0,0 +1,499 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Building AppleScript safely and reading values back from osascript.
+// Anything that isn't a literal piece of script MUST go through `Value` (or `string_literal`),
+// otherwise a path with a quote in it can run arbitrary AppleScript.
+
+use std::fmt;
+
+/// An AppleScript value, both for building scripts and for parsing `osascript -s s` output
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Real(f64),
+    Boolean(bool),
+    List(Vec<Value>),
+    // Keys are kept in order. They are written as-is, so they must be AppleScript identifiers
+    Record(Vec<(String, Value)>),
+    // `POSIX file "<path>"`
+    PosixFile(String),
+    Missing,
+    // Anything we don't understand (aliases, application references, ...), as source text
+    Other(String),
+}
+
+/// Quotes and escapes a string into an AppleScript string literal
+pub fn string_literal(s: &str) -> String {
+    let mut literal = String::with_capacity(s.len() + 2);
+    literal.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            _ => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{}", string_literal(s)),
+            Value::Integer(i) => write!(f, "{i}"),
+            Value::Real(r) => write!(f, "{r:?}"),
+            Value::Boolean(b) => write!(f, "{b}"),
+            Value::List(items) => {
+                write!(f, "{{")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "}}")
+            }
+            Value::Record(fields) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{key}:{value}")?;
+                }
+                write!(f, "}}")
+            }
+            Value::PosixFile(path) => write!(f, "POSIX file {}", string_literal(path)),
+            Value::Missing => write!(f, "missing value"),
+            Value::Other(source) => write!(f, "{source}"),
+        }
+    }
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// Integers are reals too
+    pub fn as_real(&self) -> Option<f64> {
+        match self {
+            Value::Real(r) => Some(*r),
+            Value::Integer(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// A field of a record
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Record(fields) => fields
+                .iter()
+                .find(|(field, _)| field == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Expected {token:?} at {:?} in AppleScript value",
+                self.rest()
+            ))
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect("\"")?;
+
+        let mut s = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(s);
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => s.push('\n'),
+                    Some((_, 'r')) => s.push('\r'),
+                    Some((_, 't')) => s.push('\t'),
+                    Some((_, c)) => s.push(c),
+                    None => break,
+                },
+                _ => s.push(c),
+            }
+        }
+
+        Err(String::from("Unterminated string in AppleScript value"))
+    }
+
+    /// Everything up to the next `,` or `}` that isn't inside a string
+    fn bare(&mut self) -> &'a str {
+        let rest = self.rest();
+        let mut in_string = false;
+        let mut escaped = false;
+        let mut end = rest.len();
+
+        for (i, c) in rest.char_indices() {
+            if escaped {
+                escaped = false;
+            } else if in_string {
+                match c {
+                    '\\' => escaped = true,
+                    '"' => in_string = false,
+                    _ => {}
+                }
+            } else {
+                match c {
+                    '"' => in_string = true,
+                    ',' | '}' => {
+                        end = i;
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        self.pos += end;
+        rest[..end].trim()
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+
+        if self.rest().starts_with('"') {
+            return Ok(Value::String(self.string()?));
+        }
+        if self.eat("{") {
+            return self.list_or_record();
+        }
+
+        let bare = self.bare();
+        Ok(match bare {
+            "true" => Value::Boolean(true),
+            "false" => Value::Boolean(false),
+            "missing value" => Value::Missing,
+            _ => {
+                if let Ok(i) = bare.parse() {
+                    Value::Integer(i)
+                } else if let Ok(r) = bare.parse() {
+                    Value::Real(r)
+                } else if let Some(path) = bare.strip_prefix("POSIX file ") {
+                    let mut inner = Parser {
+                        input: path,
+                        pos: 0,
+                    };
+                    Value::PosixFile(inner.string()?)
+                } else {
+                    Value::Other(bare.to_string())
+                }
+            }
+        })
+    }
+
+    fn list_or_record(&mut self) -> Result<Value, String> {
+        if self.eat("}") {
+            return Ok(Value::List(Vec::new()));
+        }
+
+        // Records start with `key:`
+        let rest = self.rest();
+        let is_record = match (rest.find(':'), rest.find(['"', ',', '{', '}'])) {
+            (Some(colon), Some(other)) => colon < other,
+            (Some(_), None) => true,
+            _ => false,
+        };
+
+        if is_record {
+            let mut fields = Vec::new();
+            loop {
+                self.skip_whitespace();
+                let rest = self.rest();
+                let colon = rest
+                    .find(':')
+                    .ok_or_else(|| String::from("Expected a record key"))?;
+                let key = rest[..colon].trim().to_string();
+                self.pos += colon + 1;
+
+                fields.push((key, self.value()?));
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            self.expect("}")?;
+            Ok(Value::Record(fields))
+        } else {
+            let mut items = Vec::new();
+            loop {
+                items.push(self.value()?);
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            self.expect("}")?;
+            Ok(Value::List(items))
+        }
+    }
+}
+
+/// Parses the output of `osascript -s s`
+pub fn parse(output: &str) -> Result<Value, String> {
+    let output = output.trim();
+    if output.is_empty() {
+        return Ok(Value::Missing);
+    }
+
+    let mut parser = Parser {
+        input: output,
+        pos: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if !parser.rest().is_empty() {
+        return Err(format!(
+            "Trailing data {:?} in AppleScript value",
+            parser.rest()
+        ));
+    }
+    Ok(value)
+}
+
+/// Runs a script with osascript and parses what it returns
+pub fn run(script: &str) -> Result<Value, String> {
+    log::debug!("Running AppleScript: {script}");
+
+    let output = match std::process::Command::new("osascript")
+        .arg("-s")
+        .arg("s")
+        .arg("-e")
+        .arg(script)
+        .output()
+    {
+        Ok(o) => o,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    match String::from_utf8(output.stdout) {
+        Ok(o) => parse(&o),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn string_literal_escapes_quotes_and_backslashes() {
+        assert_eq!(string_literal("plain"), r#""plain""#);
+        assert_eq!(
+            string_literal(r#"a "quoted" \ path"#),
+            r#""a \"quoted\" \\ path""#
+        );
+        // A quote can't end the literal early and start a command
+        assert_eq!(
+            string_literal(r#"" & (do shell script "id") & ""#),
+            r#""\" & (do shell script \"id\") & \"""#
+        );
+    }
+
+    #[test]
+    fn posix_file_escapes_its_path() {
+        assert_eq!(
+            Value::PosixFile(r#"/tmp/a "b"\c.png"#.to_string()).to_string(),
+            r#"POSIX file "/tmp/a \"b\"\\c.png""#
+        );
+    }
+
+    #[test]
+    fn escaped_values_round_trip() {
+        for value in [
+            string(r#"say "hi" \ bye"#),
+            string("/Users/me/Pictures/a, b} c: d.png"),
+            Value::PosixFile(r#"/tmp/a "b"\c.png"#.to_string()),
+            Value::List(vec![string(r#"\""#), Value::Integer(-3), Value::Real(-0.5)]),
+            Value::Record(vec![
+                (String::from("name"), string("x:y")),
+                (String::from("flag"), Value::Boolean(true)),
+            ]),
+        ] {
+            assert_eq!(parse(&value.to_string()), Ok(value));
+        }
+    }
+
+    #[test]
+    fn record_with_spaced_keys() {
+        let value =
+            parse("{output volume:50, input volume:missing value, output muted:false}\n").unwrap();
+
+        assert_eq!(value.get("output volume"), Some(&Value::Integer(50)));
+        assert_eq!(value.get("input volume"), Some(&Value::Missing));
+        assert_eq!(value.get("output muted"), Some(&Value::Boolean(false)));
+        assert_eq!(value.get("alert volume"), None);
+    }
+
+    #[test]
+    fn nested_lists_and_records() {
+        assert_eq!(
+            parse(r#"{{1, 2}, {a:{b:"c"}, d:{}}, {}}"#),
+            Ok(Value::List(vec![
+                Value::List(vec![Value::Integer(1), Value::Integer(2)]),
+                Value::Record(vec![
+                    (
+                        String::from("a"),
+                        Value::Record(vec![(String::from("b"), string("c"))])
+                    ),
+                    (String::from("d"), Value::List(Vec::new())),
+                ]),
+                Value::List(Vec::new()),
+            ]))
+        );
+    }
+
+    #[test]
+    fn strings_with_separators() {
+        assert_eq!(
+            parse(r#"{"a, b", "}", "c:d"}"#),
+            Ok(Value::List(vec![
+                string("a, b"),
+                string("}"),
+                string("c:d")
+            ]))
+        );
+        assert_eq!(
+            parse(r#"{name:"x, y}: z", id:1}"#),
+            Ok(Value::Record(vec![
+                (String::from("name"), string("x, y}: z")),
+                (String::from("id"), Value::Integer(1)),
+            ]))
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(parse("-12"), Ok(Value::Integer(-12)));
+        assert_eq!(parse("-0.25"), Ok(Value::Real(-0.25)));
+        assert_eq!(parse("1.5E+3"), Ok(Value::Real(1500.0)));
+        assert_eq!(
+            parse("{-1, -2.5}").unwrap().as_list().unwrap()[1].as_real(),
+            Some(-2.5)
+        );
+    }
+
+    #[test]
+    fn posix_file() {
+        assert_eq!(
+            parse(r#"POSIX file "/Library/Desktop Pictures/Sonoma.heic""#),
+            Ok(Value::PosixFile(String::from(
+                "/Library/Desktop Pictures/Sonoma.heic"
+            )))
+        );
+        assert_eq!(
+            parse(r#"{POSIX file "/a, b.png", 1}"#),
+            Ok(Value::List(vec![
+                Value::PosixFile(String::from("/a, b.png")),
+                Value::Integer(1),
+            ]))
+        );
+    }
+
+    #[test]
+    fn empty_output_is_missing_value() {
+        assert_eq!(parse("\n"), Ok(Value::Missing));
+    }
+
+    #[test]
+    fn unterminated_string() {
+        assert!(parse(r#""abc"#).is_err());
+        assert!(parse(r#""abc\""#).is_err());
+        assert!(parse(r#"{"abc}"#).is_err());
+    }
+
+    #[test]
+    fn trailing_data() {
+        assert!(parse(r#""abc" def"#).is_err());
+        assert!(parse("{1, 2}}").is_err());
+        assert!(parse("{a:1} {b:2}").is_err());
+    }
+}
@@ -93,3 +93,94 @@ macro_rules! run_command_option {
         }
     };
 }
+
+/// The user's home directory
+pub fn home_dir() -> Result<std::path::PathBuf, String> {
+    match std::env::var("HOME") {
+        Ok(homedir) => Ok(homedir.into()),
+        Err(_) => Err(String::from("HOME not set")),
+    }
+}
+
+/// PrefSuite's own directory in ~/Library/Application Support. Not created here
+pub fn app_support_dir() -> Result<std::path::PathBuf, String> {
+    Ok(home_dir()?.join("Library/Application Support/PrefSuite"))
+}
+
+/// PrefSuite's own directory in ~/Library/Caches. Not created here
+pub fn cache_dir() -> Result<std::path::PathBuf, String> {
+    Ok(home_dir()?.join("Library/Caches/PrefSuite"))
+}
+
+/// Decodes the value printed by `nvram`, where non-printable bytes are percent-escaped
+pub fn decode_nvram_value(value: &str) -> Result<Vec<u8>, String> {
+    let raw = value.as_bytes();
+    let mut bytes = Vec::new();
+    let mut i = 0;
+
+    while i < raw.len() {
+        if raw[i] == b'%' {
+            let hex = value
+                .get(i + 1..i + 3)
+                .ok_or_else(|| format!("Truncated escape in {value:?}"))?;
+            bytes.push(u8::from_str_radix(hex, 16).map_err(|e| e.to_string())?);
+            i += 3;
+        } else {
+            bytes.push(raw[i]);
+            i += 1;
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Quotes a string so `sh` treats it as a single literal word
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+/// Runs a command as root, showing the macOS authorization prompt.
+/// Returns the command's output.
+pub fn run_as_admin(program: &str, args: &[&str]) -> Result<String, String> {
+    let mut script = shell_quote(program);
+    for arg in args {
+        script.push(' ');
+        script.push_str(&shell_quote(arg));
+    }
+    log::info!("Running as admin: {script}");
+
+    let output = crate::app::applescript::run(&format!(
+        "do shell script {} with administrator privileges",
+        crate::app::applescript::Value::String(script)
+    ));
+
+    match output {
+        Ok(value) => Ok(value.as_str().unwrap_or_default().to_string()),
+        // "execution error: User canceled. (-128)"
+        Err(e) if e.contains("(-128)") => Err("Authorization was cancelled.".into()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_plain_and_escaped_bytes() {
+        assert_eq!(decode_nvram_value("").unwrap(), b"");
+        assert_eq!(decode_nvram_value("abc").unwrap(), b"abc");
+        assert_eq!(decode_nvram_value("%00%01").unwrap(), [0, 1]);
+        assert_eq!(
+            decode_nvram_value("a%ffb%7F").unwrap(),
+            [b'a', 0xff, b'b', 0x7f]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_escapes() {
+        assert!(decode_nvram_value("%").is_err());
+        assert!(decode_nvram_value("%0").is_err());
+        assert!(decode_nvram_value("%zz").is_err());
+    }
+}
@@ -19,6 +19,7 @@
 pub mod about;
 pub mod bluetooth;
 pub mod main_menu;
+pub mod security;
 pub mod sip;
 pub mod sound;
 pub mod wallpaper;
@@ -15,62 +15,214 @@
     You should have received a copy of the GNU General Public License
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
+
+mod inquiry;
+mod paired;
+
 use crate::app::{Menu, MyApp};
-use std::collections::HashSet;
-use log::{debug, error, info, trace};
 use eframe::egui;
 use eframe::egui::RichText;
+use inquiry::{Inquiry, InquiryEvent, InquirySettings, NearbyDevice};
+use paired::PairedDevice;
+use std::sync::mpsc::{Receiver, channel};
+
+#[derive(Default)]
+pub struct BluetoothData {
+    // Devices found by the last scan, in the order they were found
+    nearby: Vec<NearbyDevice>,
+    // The running scan, if any
+    inquiry: Option<Inquiry>,
+    inquiry_settings: InquirySettings,
+    // How the last scan ended
+    inquiry_result: Option<Result<(), String>>,
+    paired: Option<Result<Vec<PairedDevice>, String>>,
+    // The paired devices being read on a background thread
+    paired_lookup: Option<Receiver<Result<Vec<PairedDevice>, String>>>,
+}
+
+impl BluetoothData {
+    fn receive_events(&mut self) {
+        let Some(inquiry) = &self.inquiry else {
+            return;
+        };
+
+        let mut finished = None;
+        for event in inquiry.events() {
+            match event {
+                InquiryEvent::Found(device) => {
+                    match self
+                        .nearby
+                        .iter_mut()
+                        .find(|nearby| nearby.address == device.address)
+                    {
+                        Some(nearby) => *nearby = device,
+                        None => self.nearby.push(device),
+                    }
+                }
+                InquiryEvent::Finished(res) => finished = Some(res),
+            }
+        }
+
+        if finished.is_some() {
+            self.inquiry = None;
+            self.inquiry_result = finished;
+        }
+    }
+}
 
-#[cfg(target_os = "macos")]
-fn get_nearby_bluetooth() -> Result<HashSet<String>, String> {
-    use objc2_io_bluetooth::IOBluetoothDevice;
-    info!("Scanning for bluetooth devices");
+/// Runs `paired::get_paired_devices` on a background thread
+fn lookup_paired(ctx: &egui::Context) -> Receiver<Result<Vec<PairedDevice>, String>> {
+    let (sender, receiver) = channel();
+    let ctx = ctx.clone();
 
-    let inquiry = unsafe { objc2_io_bluetooth::IOBluetoothDeviceInquiry::new() };
+    std::thread::spawn(move || {
+        // Nobody is waiting anymore if this fails
+        let _ = sender.send(paired::get_paired_devices());
+        ctx.request_repaint();
+    });
 
-    trace!("Aquired IOBluetoothDeviceInquiry");
-    //inquiry.setInquiryLength()
-    //inquiry.setUpdateNewDeviceNames()
-    unsafe {
-        inquiry.start();
+    receiver
+}
+
+fn show_paired(ui: &mut egui::Ui, data: &mut BluetoothData) {
+    let loading = data.paired_lookup.is_some();
+    if ui
+        .add_enabled(!loading, egui::Button::new("Reload"))
+        .clicked()
+        || (data.paired.is_none() && !loading)
+    {
+        data.paired_lookup = Some(lookup_paired(ui.ctx()));
+    }
+    if let Some(lookup) = &data.paired_lookup
+        && let Ok(res) = lookup.try_recv()
+    {
+        data.paired = Some(res);
+        data.paired_lookup = None;
     }
 
-    // TODO: Temp
-    std::thread::sleep(std::time::Duration::from_secs(11));
-    let devices = match unsafe { inquiry.foundDevices() } {
-        Some(devices) => devices,
+    let devices = match &data.paired {
         None => {
-            error!("Error unwrapping found devices!");
-            return Err("Error unwrapping found devices!".into());
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Loading paired devices...");
+            });
+            return;
+        }
+        Some(Ok(devices)) if devices.is_empty() => {
+            ui.label("No paired devices.");
+            return;
+        }
+        Some(Ok(devices)) => devices,
+        Some(Err(e)) => {
+            ui.label(format!("Failed to get paired devices: {e}"));
+            return;
         }
     };
 
-    info!("Found {} bluetooth devices.", devices.len());
-
-    let mut device_names = HashSet::new();
-
-    for item in devices {
-        // See https://github.com/madsmtm/objc2/issues/743
-        let device = item.downcast::<IOBluetoothDevice>().unwrap();
+    egui::Grid::new("paired_bluetooth")
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label(RichText::new("Name").strong());
+            ui.label(RichText::new("Address").strong());
+            ui.label(RichText::new("Type").strong());
+            ui.label(RichText::new("State").strong());
+            ui.label(RichText::new("Firmware").strong());
+            ui.label(RichText::new("Battery").strong());
+            ui.end_row();
+
+            for device in devices {
+                ui.label(&device.name);
+                ui.monospace(&device.address);
+                ui.label(device.device_type.as_deref().unwrap_or("Unknown"));
+                ui.label(if device.connected {
+                    "Connected"
+                } else {
+                    "Not connected"
+                });
+                ui.label(device.firmware.as_deref().unwrap_or(""));
+                ui.label(
+                    device
+                        .batteries
+                        .iter()
+                        .map(|battery| {
+                            if battery.component.is_empty() {
+                                format!("{}%", battery.percent)
+                            } else {
+                                format!("{}: {}%", battery.component, battery.percent)
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                );
+                ui.end_row();
+            }
+        });
+}
 
-        let devname = unsafe { device.name() };
+fn show_nearby(ui: &mut egui::Ui, data: &mut BluetoothData) {
+    let scanning = data.inquiry.is_some();
 
-        debug!("Found Bluetooth device: {}", devname);
+    ui.add_enabled_ui(!scanning, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Scan for");
+            ui.add(egui::DragValue::new(&mut data.inquiry_settings.length).range(1..=60));
+            ui.label("seconds");
+        });
+        ui.checkbox(
+            &mut data.inquiry_settings.update_names,
+            "Look up device names",
+        );
+    });
 
-        let name = devname.to_string();
+    ui.horizontal(|ui| {
+        if scanning {
+            ui.spinner();
+            if ui.button("Cancel").clicked()
+                && let Some(inquiry) = &data.inquiry
+            {
+                inquiry.cancel();
+            }
+        } else if ui.button("Scan").clicked() {
+            data.nearby.clear();
+            data.inquiry_result = None;
+            data.inquiry = Some(Inquiry::start(ui.ctx(), data.inquiry_settings));
+        }
+    });
 
-        device_names.insert(name);
+    if let Some(Err(e)) = &data.inquiry_result {
+        ui.label(format!("Scan failed: {e}"));
     }
 
-    Ok(device_names)
-}
+    if data.nearby.is_empty() {
+        if data.inquiry_result.is_some() {
+            ui.label("No devices found.");
+        }
+        return;
+    }
 
-#[cfg(not(target_os = "macos"))]
-fn get_nearby_bluetooth() -> Result<HashSet<String>, String> {
-    Err("Bluetooth is not supported on this system.".into())
+    egui::Grid::new("nearby_bluetooth")
+        .striped(true)
+        .show(ui, |ui| {
+            ui.label(RichText::new("Name").strong());
+            ui.label(RichText::new("Address").strong());
+            ui.label(RichText::new("Signal").strong());
+            ui.end_row();
+            for device in &data.nearby {
+                ui.label(if device.name.is_empty() {
+                    "(Unknown)"
+                } else {
+                    &device.name
+                });
+                ui.monospace(&device.address);
+                ui.label(format!("{} dBm", device.rssi));
+                ui.end_row();
+            }
+        });
 }
 
 pub fn main(app: &mut MyApp, ctx: &egui::Context) {
+    app.bluetooth_data.receive_events();
+
     egui::CentralPanel::default().show(ctx, |ui| {
         if ui.button(RichText::new("Back")).clicked() {
             app.selected_menu = Menu::Main;
@@ -79,10 +231,13 @@ pub fn main(app: &mut MyApp, ctx: &egui::Context) {
             ui.label(RichText::new("Bluetooth Menu:").size(36.0));
         });
 
-        if ui.button("Scan").clicked() {
-            for item in get_nearby_bluetooth().unwrap() {
-                ui.label(item);
-            }
-        }
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.collapsing("Paired devices", |ui| {
+                show_paired(ui, &mut app.bluetooth_data);
+            });
+
+            ui.label(RichText::new("Nearby devices:").heading());
+            show_nearby(ui, &mut app.bluetooth_data);
+        });
     });
 }
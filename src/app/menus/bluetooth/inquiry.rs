@@ -0,0 +1,188 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Scanning for nearby Bluetooth devices on a background thread.
+// IOBluetoothDeviceInquiry reports through the run loop of the thread that started it,
+// so the thread runs its run loop in short slices and checks what was found in between.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+use eframe::egui;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct NearbyDevice {
+    // Can be empty until the name is looked up
+    pub name: String,
+    pub address: String,
+    // In dBm
+    pub rssi: i8,
+}
+
+pub enum InquiryEvent {
+    // Sent again when a device's name or signal changes
+    #[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+    Found(NearbyDevice),
+    Finished(Result<(), String>),
+}
+
+#[derive(Clone, Copy)]
+pub struct InquirySettings {
+    // In seconds
+    pub length: u8,
+    // Look up the names of found devices (makes the scan take longer)
+    pub update_names: bool,
+}
+
+impl Default for InquirySettings {
+    fn default() -> Self {
+        Self {
+            // What IOBluetoothDeviceInquiry uses by default
+            length: 10,
+            update_names: true,
+        }
+    }
+}
+
+/// A running inquiry. Cancelled when dropped
+pub struct Inquiry {
+    receiver: Receiver<InquiryEvent>,
+    cancel: Arc<AtomicBool>,
+}
+
+#[cfg(target_os = "macos")]
+fn run_inquiry(
+    settings: InquirySettings,
+    cancel: &AtomicBool,
+    events: &Sender<InquiryEvent>,
+    ctx: &egui::Context,
+) -> Result<(), String> {
+    use log::{debug, info};
+    use objc2_foundation::{NSDate, NSRunLoop};
+    use objc2_io_bluetooth::{IOBluetoothDevice, IOBluetoothDeviceInquiry};
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    // How long each slice of the run loop is
+    const SLICE: f64 = 0.25;
+    // Name lookups happen after the inquiry itself
+    const NAME_LOOKUP_TIME: Duration = Duration::from_secs(5);
+
+    info!("Scanning for bluetooth devices for {}s", settings.length);
+
+    let inquiry = unsafe { IOBluetoothDeviceInquiry::new() };
+    unsafe {
+        inquiry.setInquiryLength(settings.length);
+        inquiry.setUpdateNewDeviceNames(settings.update_names);
+    }
+
+    let status = unsafe { inquiry.start() };
+    if status != 0 {
+        return Err(format!(
+            "Could not start the inquiry (IOReturn {status:#x})"
+        ));
+    }
+
+    let mut duration = Duration::from_secs(settings.length.into());
+    if settings.update_names {
+        duration += NAME_LOOKUP_TIME;
+    }
+
+    let run_loop = NSRunLoop::currentRunLoop();
+    let start = Instant::now();
+    // Everything sent so far, by address
+    let mut sent: HashMap<String, NearbyDevice> = HashMap::new();
+
+    while start.elapsed() < duration && !cancel.load(Ordering::Relaxed) {
+        run_loop.runUntilDate(&NSDate::dateWithTimeIntervalSinceNow(SLICE));
+
+        let Some(found) = (unsafe { inquiry.foundDevices() }) else {
+            continue;
+        };
+        for item in found {
+            // See https://github.com/madsmtm/objc2/issues/743
+            let Ok(device) = item.downcast::<IOBluetoothDevice>() else {
+                continue;
+            };
+
+            let device = NearbyDevice {
+                name: unsafe { device.name() }.to_string(),
+                address: unsafe { device.addressString() }.to_string(),
+                rssi: unsafe { device.rawRSSI() },
+            };
+            if sent.get(&device.address) != Some(&device) {
+                debug!("Found Bluetooth device: {device:?}");
+                sent.insert(device.address.clone(), device.clone());
+                if events.send(InquiryEvent::Found(device)).is_err() {
+                    // Nobody is listening anymore
+                    break;
+                }
+                ctx.request_repaint();
+            }
+        }
+    }
+
+    unsafe {
+        inquiry.stop();
+    }
+    info!("Found {} bluetooth devices.", sent.len());
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn run_inquiry(
+    _settings: InquirySettings,
+    _cancel: &AtomicBool,
+    _events: &Sender<InquiryEvent>,
+    _ctx: &egui::Context,
+) -> Result<(), String> {
+    Err("Bluetooth is not supported on this system.".into())
+}
+
+impl Inquiry {
+    pub fn start(ctx: &egui::Context, settings: InquirySettings) -> Self {
+        let (events, receiver) = channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let ctx = ctx.clone();
+        let thread_cancel = cancel.clone();
+        std::thread::spawn(move || {
+            let res = run_inquiry(settings, &thread_cancel, &events, &ctx);
+            let _ = events.send(InquiryEvent::Finished(res));
+            ctx.request_repaint();
+        });
+
+        Self { receiver, cancel }
+    }
+
+    /// Stops the scan. A `Finished` event still follows
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = InquiryEvent> + '_ {
+        self.receiver.try_iter()
+    }
+}
+
+impl Drop for Inquiry {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
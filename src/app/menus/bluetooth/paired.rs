@@ -0,0 +1,246 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Paired Bluetooth devices. system_profiler knows the most (battery, firmware), and IOBluetooth
+// fills in devices it leaves out.
+//
+// `system_profiler -json SPBluetoothDataType` (macOS 12 and later) looks like:
+//   { "SPBluetoothDataType": [ {
+//       "device_connected":     [ { "<name>": { "device_address": ..., "device_batteryLevelLeft": "90%", ... } } ],
+//       "device_not_connected": [ { "<name>": { ... } } ]
+//   } ] }
+
+use crate::command_output;
+
+const BATTERY_PREFIX: &str = "device_batteryLevel";
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct BatteryLevel {
+    // Like "Left", "Right" or "Case". Empty for devices with a single battery
+    pub component: String,
+    pub percent: u8,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PairedDevice {
+    pub name: String,
+    // Like "AA:BB:CC:DD:EE:FF"
+    pub address: String,
+    // Like "Headphones" or "Keyboard"
+    pub device_type: Option<String>,
+    pub connected: bool,
+    pub firmware: Option<String>,
+    pub batteries: Vec<BatteryLevel>,
+}
+
+/// IOBluetooth writes addresses like "aa-bb-cc-dd-ee-ff", system_profiler like "AA:BB:CC:DD:EE:FF"
+pub fn normalize_address(address: &str) -> String {
+    address.to_uppercase().replace('-', ":")
+}
+
+/// Parses "85%" or "85 %"
+fn parse_percent(value: &str) -> Option<u8> {
+    value.trim().trim_end_matches('%').trim().parse().ok()
+}
+
+fn parse_device(name: &str, properties: &json::JsonValue, connected: bool) -> PairedDevice {
+    let string = |key: &str| properties[key].as_str().map(str::to_string);
+
+    let mut batteries: Vec<BatteryLevel> = properties
+        .entries()
+        .filter_map(|(key, value)| {
+            let component = key.strip_prefix(BATTERY_PREFIX)?;
+            Some(BatteryLevel {
+                // "Main" is the only battery
+                component: if component == "Main" {
+                    String::new()
+                } else {
+                    component.to_string()
+                },
+                percent: parse_percent(value.as_str()?)?,
+            })
+        })
+        .collect();
+    batteries.sort_by(|a, b| a.component.cmp(&b.component));
+
+    PairedDevice {
+        name: name.to_string(),
+        address: normalize_address(&string("device_address").unwrap_or_default()),
+        device_type: string("device_minorType"),
+        connected,
+        firmware: string("device_firmwareVersion"),
+        batteries,
+    }
+}
+
+/// Parses the output of `system_profiler -json SPBluetoothDataType`
+pub fn parse_paired_devices(output: &str) -> Result<Vec<PairedDevice>, String> {
+    let value = json::parse(output).map_err(|e| e.to_string())?;
+
+    let mut devices = Vec::new();
+    for controller in value["SPBluetoothDataType"].members() {
+        for (key, connected) in [("device_connected", true), ("device_not_connected", false)] {
+            // A list of single-entry objects, named after the device
+            for entry in controller[key].members() {
+                for (name, properties) in entry.entries() {
+                    devices.push(parse_device(name, properties, connected));
+                }
+            }
+        }
+    }
+
+    Ok(devices)
+}
+
+/// Paired devices according to IOBluetooth, as (name, address, connected)
+#[cfg(target_os = "macos")]
+fn paired_devices_ffi() -> Vec<(String, String, bool)> {
+    use objc2_io_bluetooth::IOBluetoothDevice;
+
+    let Some(paired) = (unsafe { IOBluetoothDevice::pairedDevices() }) else {
+        return Vec::new();
+    };
+
+    paired
+        .into_iter()
+        // See https://github.com/madsmtm/objc2/issues/743
+        .filter_map(|item| item.downcast::<IOBluetoothDevice>().ok())
+        .map(|device| unsafe {
+            (
+                device.name().to_string(),
+                normalize_address(&device.addressString().to_string()),
+                device.isConnected(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "macos"))]
+fn paired_devices_ffi() -> Vec<(String, String, bool)> {
+    Vec::new()
+}
+
+/// Slow (system_profiler takes a second or two)
+pub fn get_paired_devices() -> Result<Vec<PairedDevice>, String> {
+    let mut devices = parse_paired_devices(&command_output!(
+        "system_profiler",
+        "-json",
+        "SPBluetoothDataType"
+    ))?;
+
+    for (name, address, connected) in paired_devices_ffi() {
+        if !devices.iter().any(|device| device.address == address) {
+            devices.push(PairedDevice {
+                name,
+                address,
+                device_type: None,
+                connected,
+                firmware: None,
+                batteries: Vec::new(),
+            });
+        }
+    }
+
+    // Connected devices first
+    devices.sort_by_key(|device| !device.connected);
+    Ok(devices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = r#"{
+  "SPBluetoothDataType" : [
+    {
+      "controller_properties" : {
+        "controller_address" : "11:22:33:44:55:66",
+        "controller_state" : "attrib_on"
+      },
+      "device_connected" : [
+        {
+          "AirPods Pro" : {
+            "device_address" : "aa-bb-cc-dd-ee-ff",
+            "device_batteryLevelCase" : "40%",
+            "device_batteryLevelLeft" : "90%",
+            "device_batteryLevelRight" : "100%",
+            "device_firmwareVersion" : "6A321",
+            "device_minorType" : "Headphones"
+          }
+        }
+      ],
+      "device_not_connected" : [
+        {
+          "Magic Keyboard" : {
+            "device_address" : "01:23:45:67:89:AB",
+            "device_batteryLevelMain" : "85 %",
+            "device_minorType" : "Keyboard"
+          }
+        }
+      ]
+    }
+  ]
+}"#;
+
+    fn battery(component: &str, percent: u8) -> BatteryLevel {
+        BatteryLevel {
+            component: component.to_string(),
+            percent,
+        }
+    }
+
+    #[test]
+    fn parses_connected_and_not_connected_devices() {
+        let devices = parse_paired_devices(FIXTURE).unwrap();
+
+        assert_eq!(
+            devices,
+            [
+                PairedDevice {
+                    name: String::from("AirPods Pro"),
+                    address: String::from("AA:BB:CC:DD:EE:FF"),
+                    device_type: Some(String::from("Headphones")),
+                    connected: true,
+                    firmware: Some(String::from("6A321")),
+                    batteries: vec![
+                        battery("Case", 40),
+                        battery("Left", 90),
+                        battery("Right", 100)
+                    ],
+                },
+                PairedDevice {
+                    name: String::from("Magic Keyboard"),
+                    address: String::from("01:23:45:67:89:AB"),
+                    device_type: Some(String::from("Keyboard")),
+                    connected: false,
+                    firmware: None,
+                    batteries: vec![battery("", 85)],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn no_controller_means_no_devices() {
+        assert_eq!(
+            parse_paired_devices(r#"{"SPBluetoothDataType": []}"#),
+            Ok(Vec::new())
+        );
+        assert!(parse_paired_devices("not json").is_err());
+    }
+}
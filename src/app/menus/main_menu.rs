@@ -45,6 +45,9 @@ pub fn main(app: &mut MyApp, ctx: &egui::Context) {
             {
                 app.selected_menu = Menu::Sip;
             }
+            if ui.button(RichText::new("Security").size(20.0)).clicked() {
+                app.selected_menu = Menu::Security;
+            }
         })
     });
 }
@@ -0,0 +1,462 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+mod firewall;
+
+use crate::app::{Menu, MyApp};
+use crate::command_output;
+use std::path::Path;
+use std::sync::mpsc::{Receiver, channel};
+
+use eframe::egui;
+use eframe::egui::RichText;
+use log::info;
+
+pub const SOCKETFILTERFW: &str = "/usr/libexec/ApplicationFirewall/socketfilterfw";
+
+#[derive(PartialEq)]
+pub enum FileVaultStatus {
+    On,
+    Off,
+    // Percent completed, if reported
+    Encrypting(Option<f32>),
+    Decrypting(Option<f32>),
+}
+
+#[derive(PartialEq)]
+pub enum FirewallState {
+    Off,
+    On,
+    // Blocking all incoming connections
+    BlockAll,
+}
+
+pub struct FirewallStatus {
+    pub state: FirewallState,
+    pub stealth: bool,
+}
+
+struct SecurityReport {
+    // Whether Gatekeeper assessments are enabled
+    gatekeeper: Result<bool, String>,
+    filevault: Result<FileVaultStatus, String>,
+    firewall: Result<FirewallStatus, String>,
+    // Whether the Sealed System Volume is enforced. None before Big Sur
+    ssv: Option<Result<bool, String>>,
+    xprotect: Result<String, String>,
+}
+
+#[derive(Default)]
+pub struct SecurityData {
+    // Security report cache
+    report: Option<SecurityReport>,
+    // The report being collected on a background thread
+    report_lookup: Option<Receiver<SecurityReport>>,
+    firewall_data: firewall::FirewallData,
+}
+
+pub fn parse_spctl_status(output: &str) -> Result<bool, String> {
+    match output.trim() {
+        "assessments enabled" => Ok(true),
+        "assessments disabled" => Ok(false),
+        other => Err(format!("Unexpected spctl output: {other:?}")),
+    }
+}
+
+fn get_gatekeeper() -> Result<bool, String> {
+    parse_spctl_status(&command_output!("spctl", "--status"))
+}
+
+/// Pulls the number out of "Percent completed = 45.3"
+fn parse_percent(output: &str) -> Option<f32> {
+    output
+        .split_once("Percent completed = ")
+        .and_then(|(_, percent)| percent.split_whitespace().next())
+        .and_then(|percent| percent.parse().ok())
+}
+
+pub fn parse_fdesetup_status(output: &str) -> Result<FileVaultStatus, String> {
+    if output.contains("Encryption in progress") {
+        Ok(FileVaultStatus::Encrypting(parse_percent(output)))
+    } else if output.contains("Decryption in progress") {
+        Ok(FileVaultStatus::Decrypting(parse_percent(output)))
+    } else if output.contains("FileVault is On.") {
+        Ok(FileVaultStatus::On)
+    } else if output.contains("FileVault is Off.") {
+        Ok(FileVaultStatus::Off)
+    } else {
+        Err(format!("Unexpected fdesetup output: {:?}", output.trim()))
+    }
+}
+
+fn get_filevault() -> Result<FileVaultStatus, String> {
+    parse_fdesetup_status(&command_output!("fdesetup", "status"))
+}
+
+pub fn parse_firewall_state(output: &str) -> Result<FirewallState, String> {
+    // "Firewall is enabled. (State = 1)"
+    let state = output
+        .split_once("(State = ")
+        .and_then(|(_, state)| state.split(')').next());
+
+    match state {
+        Some("0") => Ok(FirewallState::Off),
+        Some("1") => Ok(FirewallState::On),
+        Some("2") => Ok(FirewallState::BlockAll),
+        // Newer versions drop the state number
+        _ if output.contains("Firewall is enabled") => Ok(FirewallState::On),
+        _ if output.contains("Firewall is disabled") => Ok(FirewallState::Off),
+        _ if output.contains("blocking all") => Ok(FirewallState::BlockAll),
+        _ => Err(format!(
+            "Unexpected socketfilterfw output: {:?}",
+            output.trim()
+        )),
+    }
+}
+
+pub fn parse_stealth_mode(output: &str) -> Result<bool, String> {
+    // "Stealth mode enabled" on older versions, "Firewall stealth mode is on" on newer ones
+    let output = output.trim();
+    if output.ends_with("enabled") || output.ends_with("is on") {
+        Ok(true)
+    } else if output.ends_with("disabled") || output.ends_with("is off") {
+        Ok(false)
+    } else {
+        Err(format!("Unexpected socketfilterfw output: {output:?}"))
+    }
+}
+
+pub fn get_firewall() -> Result<FirewallStatus, String> {
+    Ok(FirewallStatus {
+        state: parse_firewall_state(&command_output!(SOCKETFILTERFW, "--getglobalstate"))?,
+        stealth: parse_stealth_mode(&command_output!(SOCKETFILTERFW, "--getstealthmode"))?,
+    })
+}
+
+pub fn parse_authenticated_root_status(output: &str) -> Result<bool, String> {
+    match output.trim().strip_prefix("Authenticated Root status: ") {
+        Some("enabled") => Ok(true),
+        Some("disabled") => Ok(false),
+        _ => Err(format!("Unexpected csrutil output: {:?}", output.trim())),
+    }
+}
+
+fn get_ssv() -> Result<bool, String> {
+    parse_authenticated_root_status(&command_output!("csrutil", "authenticated-root", "status"))
+}
+
+fn get_xprotect_version() -> Result<String, String> {
+    // XProtect moved out of the system volume in Catalina
+    let plist = [
+        "/Library/Apple/System/Library/CoreServices/XProtect.bundle/Contents/Info.plist",
+        "/System/Library/CoreServices/XProtect.bundle/Contents/Info.plist",
+    ]
+    .into_iter()
+    .find(|plist| Path::new(plist).exists())
+    .ok_or_else(|| String::from("XProtect not found"))?;
+
+    let version = command_output!("defaults", "read", plist, "CFBundleShortVersionString");
+    let version = version.trim();
+    if version.is_empty() {
+        Err(String::from("Could not read the XProtect version"))
+    } else {
+        Ok(version.to_string())
+    }
+}
+
+fn get_security_report(version: &os_info::Version) -> SecurityReport {
+    info!("Collecting security report");
+
+    SecurityReport {
+        gatekeeper: get_gatekeeper(),
+        filevault: get_filevault(),
+        firewall: get_firewall(),
+        ssv: if version >= &os_info::Version::Semantic(11, 0, 0) {
+            Some(get_ssv())
+        } else {
+            None
+        },
+        xprotect: get_xprotect_version(),
+    }
+}
+
+/// Every check starts a process, so the report is collected on a background thread
+fn collect_security_report(
+    version: os_info::Version,
+    ctx: &egui::Context,
+) -> Receiver<SecurityReport> {
+    let (sender, receiver) = channel();
+    let ctx = ctx.clone();
+
+    std::thread::spawn(move || {
+        // Nobody is waiting anymore if this fails
+        let _ = sender.send(get_security_report(&version));
+        ctx.request_repaint();
+    });
+
+    receiver
+}
+
+impl SecurityReport {
+    /// The names of the protections that are off (or couldn't be read)
+    fn weaknesses(&self) -> Vec<&'static str> {
+        let mut weaknesses = Vec::new();
+
+        if self.gatekeeper != Ok(true) {
+            weaknesses.push("Gatekeeper");
+        }
+        if !matches!(self.filevault, Ok(FileVaultStatus::On)) {
+            weaknesses.push("FileVault");
+        }
+        if !matches!(
+            self.firewall,
+            Ok(FirewallStatus {
+                state: FirewallState::On | FirewallState::BlockAll,
+                ..
+            })
+        ) {
+            weaknesses.push("Firewall");
+        }
+        if let Some(ssv) = &self.ssv
+            && ssv != &Ok(true)
+        {
+            weaknesses.push("Sealed System Volume");
+        }
+        if self.xprotect.is_err() {
+            weaknesses.push("XProtect");
+        }
+
+        weaknesses
+    }
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on { "On" } else { "Off" }
+}
+
+fn show_report(ui: &mut egui::Ui, report: &SecurityReport) {
+    let weaknesses = report.weaknesses();
+    ui.label(
+        RichText::new(if weaknesses.is_empty() {
+            "All protections are on".to_string()
+        } else {
+            format!("Weakened: {}", weaknesses.join(", "))
+        })
+        .size(32.0),
+    );
+
+    ui.label(format!(
+        "Gatekeeper: {}",
+        match &report.gatekeeper {
+            Ok(on) => on_off(*on).to_string(),
+            Err(e) => format!("Unknown ({e})"),
+        }
+    ));
+
+    ui.label(format!(
+        "FileVault: {}",
+        match &report.filevault {
+            Ok(FileVaultStatus::On) => "On".to_string(),
+            Ok(FileVaultStatus::Off) => "Off".to_string(),
+            Ok(FileVaultStatus::Encrypting(Some(percent))) => format!("Encrypting ({percent}%)"),
+            Ok(FileVaultStatus::Encrypting(None)) => "Encrypting".to_string(),
+            Ok(FileVaultStatus::Decrypting(Some(percent))) => format!("Decrypting ({percent}%)"),
+            Ok(FileVaultStatus::Decrypting(None)) => "Decrypting".to_string(),
+            Err(e) => format!("Unknown ({e})"),
+        }
+    ));
+
+    ui.label(format!(
+        "Application Firewall: {}",
+        match &report.firewall {
+            Ok(firewall) => format!(
+                "{}, stealth mode {}",
+                match firewall.state {
+                    FirewallState::Off => "Off",
+                    FirewallState::On => "On",
+                    FirewallState::BlockAll => "Blocking all incoming connections",
+                },
+                on_off(firewall.stealth)
+            ),
+            Err(e) => format!("Unknown ({e})"),
+        }
+    ));
+
+    if let Some(ssv) = &report.ssv {
+        ui.label(format!(
+            "Sealed System Volume: {}",
+            match ssv {
+                Ok(on) => on_off(*on).to_string(),
+                Err(e) => format!("Unknown ({e})"),
+            }
+        ));
+    }
+
+    ui.label(format!(
+        "XProtect version: {}",
+        match &report.xprotect {
+            Ok(version) => version.clone(),
+            Err(e) => format!("Unknown ({e})"),
+        }
+    ));
+}
+
+pub fn main(app: &mut MyApp, ctx: &egui::Context) {
+    let binding = os_info::get();
+    let version = binding.version();
+
+    egui::CentralPanel::default().show(ctx, |ui| {
+        if ui.button(RichText::new("Back")).clicked() {
+            app.selected_menu = Menu::Main;
+        }
+        ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
+            ui.label(RichText::new("Security Menu:").size(36.0));
+        });
+
+        if let Some(lookup) = app.security_data.report_lookup.as_ref()
+            && let Ok(report) = lookup.try_recv()
+        {
+            app.security_data.report = Some(report);
+            app.security_data.report_lookup = None;
+        }
+
+        if app.security_data.report.is_none() && app.security_data.report_lookup.is_none() {
+            app.security_data.report_lookup = Some(collect_security_report(version.clone(), ctx));
+        }
+
+        if ui.button("Reload").clicked() {
+            app.security_data.report = None;
+        }
+
+        if app.security_data.report_lookup.is_some() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Collecting security report...");
+            });
+        }
+
+        let mut reload = false;
+        if let Some(report) = &app.security_data.report {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                show_report(ui, report);
+
+                if let Ok(status) = &report.firewall {
+                    ui.add_space(20.0);
+                    ui.collapsing("Application Firewall", |ui| {
+                        reload = firewall::show_firewall(
+                            ui,
+                            &mut app.security_data.firewall_data,
+                            status,
+                        );
+                    });
+                }
+            });
+        }
+        if reload {
+            app.security_data.report = None;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spctl_status() {
+        assert_eq!(parse_spctl_status("assessments enabled\n"), Ok(true));
+        assert_eq!(parse_spctl_status("assessments disabled\n"), Ok(false));
+        assert!(parse_spctl_status("").is_err());
+    }
+
+    #[test]
+    fn fdesetup_status() {
+        assert!(matches!(
+            parse_fdesetup_status("FileVault is On.\n"),
+            Ok(FileVaultStatus::On)
+        ));
+        assert!(matches!(
+            parse_fdesetup_status("FileVault is Off.\n"),
+            Ok(FileVaultStatus::Off)
+        ));
+        assert!(
+            parse_fdesetup_status(
+                "FileVault is On.\nEncryption in progress: Percent completed = 23.5\n"
+            ) == Ok(FileVaultStatus::Encrypting(Some(23.5)))
+        );
+        assert!(
+            parse_fdesetup_status(
+                "FileVault is On.\nDecryption in progress: Percent completed = 80\n"
+            ) == Ok(FileVaultStatus::Decrypting(Some(80.0)))
+        );
+        assert!(
+            parse_fdesetup_status("Encryption in progress\n")
+                == Ok(FileVaultStatus::Encrypting(None))
+        );
+        assert!(parse_fdesetup_status("Error: This command requires root\n").is_err());
+    }
+
+    #[test]
+    fn firewall_state() {
+        assert!(
+            parse_firewall_state("Firewall is disabled. (State = 0)\n") == Ok(FirewallState::Off)
+        );
+        assert!(
+            parse_firewall_state("Firewall is enabled. (State = 1)\n") == Ok(FirewallState::On)
+        );
+        assert!(
+            parse_firewall_state(
+                "Firewall is blocking all non-essential incoming connections. (State = 2)\n"
+            ) == Ok(FirewallState::BlockAll)
+        );
+        // Without the state number
+        assert!(parse_firewall_state("Firewall is enabled.\n") == Ok(FirewallState::On));
+        assert!(parse_firewall_state("Firewall is disabled.\n") == Ok(FirewallState::Off));
+        assert!(parse_firewall_state("").is_err());
+    }
+
+    #[test]
+    fn stealth_mode() {
+        // Older versions
+        assert_eq!(parse_stealth_mode("Stealth mode enabled \n"), Ok(true));
+        assert_eq!(parse_stealth_mode("Stealth mode disabled \n"), Ok(false));
+        // Newer versions
+        assert_eq!(
+            parse_stealth_mode("Firewall stealth mode is on\n"),
+            Ok(true)
+        );
+        assert_eq!(
+            parse_stealth_mode("Firewall stealth mode is off\n"),
+            Ok(false)
+        );
+        assert!(parse_stealth_mode("").is_err());
+    }
+
+    #[test]
+    fn authenticated_root_status() {
+        assert_eq!(
+            parse_authenticated_root_status("Authenticated Root status: enabled\n"),
+            Ok(true)
+        );
+        assert_eq!(
+            parse_authenticated_root_status("Authenticated Root status: disabled\n"),
+            Ok(false)
+        );
+        assert!(parse_authenticated_root_status("usage: csrutil <command>\n").is_err());
+    }
+}
@@ -0,0 +1,291 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Application firewall management through socketfilterfw
+
+use super::{FirewallState, FirewallStatus, SOCKETFILTERFW};
+use crate::app::helpers::run_as_admin;
+use crate::command_output;
+use std::sync::mpsc::{Receiver, channel};
+
+use eframe::egui;
+use eframe::egui::RichText;
+
+/// A per-app rule from `socketfilterfw --listapps`
+pub struct FirewallApp {
+    pub path: String,
+    // Whether incoming connections are allowed
+    pub allowed: bool,
+}
+
+#[derive(Default)]
+pub struct FirewallData {
+    // App rules cache
+    apps: Option<Result<Vec<FirewallApp>, String>>,
+    // The last error from changing a setting
+    error: Option<String>,
+    // A change waiting for the admin prompt on a background thread
+    change: Option<Receiver<Result<(), String>>>,
+}
+
+type Change = Box<dyn FnOnce() -> Result<(), String> + Send>;
+
+/// Every change shows the admin prompt, so it's made on a background thread
+fn start_change(change: Change, ctx: &egui::Context) -> Receiver<Result<(), String>> {
+    let (sender, receiver) = channel();
+    let ctx = ctx.clone();
+
+    std::thread::spawn(move || {
+        // Nobody is waiting anymore if this fails
+        let _ = sender.send(change());
+        ctx.request_repaint();
+    });
+
+    receiver
+}
+
+/// Parses `socketfilterfw --listapps`, which looks like:
+/// ```text
+/// ALF: total number of apps = 2
+///
+/// 1 :  /Applications/Foo.app
+///      ( Allow incoming connections )
+///
+/// 2 :  /usr/local/bin/bar
+///      ( Block incoming connections )
+/// ```
+pub fn parse_listapps(output: &str) -> Result<Vec<FirewallApp>, String> {
+    let mut apps = Vec::new();
+    let mut path: Option<String> = None;
+
+    for line in output.lines() {
+        let line = line.trim();
+
+        if let Some((number, rest)) = line.split_once(" : ")
+            && number.trim().parse::<u32>().is_ok()
+        {
+            path = Some(rest.trim().to_string());
+        } else if let Some(rule) = line
+            .strip_prefix('(')
+            .and_then(|line| line.strip_suffix(')'))
+        {
+            let Some(path) = path.take() else {
+                return Err(format!("Rule without an app: {line:?}"));
+            };
+            let allowed = match rule.trim() {
+                "Allow incoming connections" => true,
+                "Block incoming connections" => false,
+                other => return Err(format!("Unknown firewall rule: {other:?}")),
+            };
+            apps.push(FirewallApp { path, allowed });
+        }
+    }
+
+    Ok(apps)
+}
+
+fn get_apps() -> Result<Vec<FirewallApp>, String> {
+    parse_listapps(&command_output!(SOCKETFILTERFW, "--listapps"))
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on { "on" } else { "off" }
+}
+
+fn set_global_state(on: bool) -> Result<(), String> {
+    run_as_admin(SOCKETFILTERFW, &["--setglobalstate", on_off(on)]).map(|_| ())
+}
+
+fn set_stealth_mode(on: bool) -> Result<(), String> {
+    run_as_admin(SOCKETFILTERFW, &["--setstealthmode", on_off(on)]).map(|_| ())
+}
+
+fn set_block_all(on: bool) -> Result<(), String> {
+    run_as_admin(SOCKETFILTERFW, &["--setblockall", on_off(on)]).map(|_| ())
+}
+
+fn add_app(path: &str) -> Result<(), String> {
+    run_as_admin(SOCKETFILTERFW, &["--add", path]).map(|_| ())
+}
+
+fn remove_app(path: &str) -> Result<(), String> {
+    run_as_admin(SOCKETFILTERFW, &["--remove", path]).map(|_| ())
+}
+
+fn set_app_allowed(path: &str, allowed: bool) -> Result<(), String> {
+    run_as_admin(
+        SOCKETFILTERFW,
+        &[
+            if allowed {
+                "--unblockapp"
+            } else {
+                "--blockapp"
+            },
+            path,
+        ],
+    )
+    .map(|_| ())
+}
+
+/// Shows the firewall controls. Returns true if the firewall state changed and needs reloading.
+pub fn show_firewall(ui: &mut egui::Ui, data: &mut FirewallData, status: &FirewallStatus) -> bool {
+    let mut changed = false;
+    if let Some(change) = data.change.as_ref()
+        && let Ok(result) = change.try_recv()
+    {
+        data.change = None;
+        data.apps = None;
+        data.error = result.err();
+        changed = true;
+    }
+
+    let mut change: Option<Change> = None;
+    ui.add_enabled_ui(data.change.is_none(), |ui| {
+        let mut enabled = status.state != FirewallState::Off;
+        if ui.checkbox(&mut enabled, "Firewall enabled").changed() {
+            change = Some(Box::new(move || set_global_state(enabled)));
+        }
+
+        let mut stealth = status.stealth;
+        if ui.checkbox(&mut stealth, "Stealth mode").changed() {
+            change = Some(Box::new(move || set_stealth_mode(stealth)));
+        }
+
+        let mut block_all = status.state == FirewallState::BlockAll;
+        if ui
+            .checkbox(&mut block_all, "Block all incoming connections")
+            .changed()
+        {
+            change = Some(Box::new(move || set_block_all(block_all)));
+        }
+
+        ui.add_space(10.0);
+        ui.label(RichText::new("Apps:").heading());
+
+        if data.apps.is_none() {
+            data.apps = Some(get_apps());
+        }
+
+        match data.apps.as_ref().unwrap() {
+            Ok(apps) => {
+                egui::Grid::new("firewall_apps")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label(RichText::new("App").strong());
+                        ui.label(RichText::new("Incoming connections").strong());
+                        ui.end_row();
+
+                        for app in apps {
+                            ui.monospace(&app.path);
+                            ui.label(if app.allowed { "Allowed" } else { "Blocked" });
+                            if ui
+                                .button(if app.allowed { "Block" } else { "Allow" })
+                                .clicked()
+                            {
+                                let (path, allowed) = (app.path.clone(), !app.allowed);
+                                change = Some(Box::new(move || set_app_allowed(&path, allowed)));
+                            }
+                            if ui.button("Remove").clicked() {
+                                let path = app.path.clone();
+                                change = Some(Box::new(move || remove_app(&path)));
+                            }
+                            ui.end_row();
+                        }
+                    });
+            }
+            Err(e) => {
+                ui.label(format!("Failed to list firewall apps: {e}"));
+            }
+        }
+
+        if ui.button("Add App").clicked()
+            && let Some(path) = rfd::FileDialog::new().pick_file()
+        {
+            let path = path.display().to_string();
+            change = Some(Box::new(move || add_app(&path)));
+        }
+    });
+
+    if let Some(change) = change {
+        data.error = None;
+        data.change = Some(start_change(change, ui.ctx()));
+    }
+
+    if data.change.is_some() {
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label("Waiting for authorization...");
+        });
+    }
+
+    if let Some(error) = &data.error {
+        ui.label(RichText::new(format!("Failed to change firewall: {error}")).size(20.0));
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LISTAPPS: &str = "ALF: total number of apps = 3
+
+1 :  /Applications/Safari.app
+ \t ( Allow incoming connections )
+
+2 :  /usr/local/bin/some tool
+ \t ( Block incoming connections )
+
+10 :  /Applications/Utilities/Terminal.app
+ \t ( Allow incoming connections )
+";
+
+    #[test]
+    fn parses_rules() {
+        let apps = parse_listapps(LISTAPPS).unwrap();
+        let apps: Vec<_> = apps
+            .iter()
+            .map(|app| (app.path.as_str(), app.allowed))
+            .collect();
+        assert_eq!(
+            apps,
+            [
+                ("/Applications/Safari.app", true),
+                ("/usr/local/bin/some tool", false),
+                ("/Applications/Utilities/Terminal.app", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_rules() {
+        assert!(
+            parse_listapps("ALF: total number of apps = 0 \n")
+                .unwrap()
+                .is_empty()
+        );
+        assert!(parse_listapps("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn malformed_rules() {
+        assert!(parse_listapps("( Allow incoming connections )\n").is_err());
+        assert!(parse_listapps("1 :  /Applications/Safari.app\n( Maybe )\n").is_err());
+    }
+}
@@ -16,21 +16,33 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+mod csr;
+mod csrutil;
+mod sources;
+
 use crate::app::{Menu, MyApp};
 
 use eframe::egui;
 use eframe::egui::RichText;
-use log::{debug, error, info, trace};
+use log::error;
 use os_info::Version;
 
 #[derive(Default)]
 pub struct SIPData {
     // Sip bits cache
     bits: Option<u32>,
+    // The configuration picked in the csrutil command generator
+    desired: Option<u32>,
+    // csr-active-config from NVRAM (applies on next boot)
+    nvram_bits: Option<Result<Option<u32>, String>>,
+    // Parsed `csrutil status`
+    csrutil_status: Option<Result<sources::CsrutilStatus, String>>,
 }
 
 #[cfg(target_os = "macos")]
 fn get_sip() -> Result<u32, String> {
+    use log::{debug, info, trace};
+
     info!("Loading /usr/lib/libSystem.dylib");
 
     // Load the library libSystem.dylib, where the function to get the current SIP config is stored
@@ -74,56 +86,13 @@ fn get_sip() -> Result<u32, String> {
     Err("sip is not supported on this platform".to_owned())
 }
 
-fn is_sip_disabled(bits: u32, version: &Version) -> bool {
-    let sierra = Version::Semantic(10, 12, 0);
-    let high_sierra = Version::Semantic(10, 13, 0);
-    let mojave = Version::Semantic(10, 14, 0);
-    let big_sur = Version::Semantic(11, 0, 0);
-
-    // Checking for all El Capitan bits except allow_apple_internal as that can't be set
-    if (bits & 239) != 239 {
-        return false;
-    } else if version < &sierra {
-        return true;
-    }
-
-    // Check for any_recovery_os
-    if (bits & 256) == 0 {
-        return false;
-    } else if version < &high_sierra {
-        return true;
-    }
-
-    // check for unapproved_kexts
-    if !(bits & 512) == 0 {
-        return false;
-    } else if version < &mojave {
-        return true;
-    }
-
-    // check or allow_executable_policy_override
-    if (bits & 1024) == 0 {
-        return false;
-    } else if version < &big_sur {
-        return true;
-    }
-
-    // check for allow_unauthenticated_root
-    (bits & 2048) != 0
-}
-
 fn show_sip_bits(ui: &mut egui::Ui, bits: u32, version: &Version) {
-    let sierra = Version::Semantic(10, 12, 0);
-    let high_sierra = Version::Semantic(10, 13, 0);
-    let mojave = Version::Semantic(10, 14, 0);
-    let big_sur = Version::Semantic(11, 0, 0);
-
     ui.label(
         RichText::new(format!(
             "CSR/SIP is: {}",
             if bits == 0 {
                 "Fully Enabled"
-            } else if is_sip_disabled(bits, version) {
+            } else if csr::is_sip_disabled(bits, version) {
                 "Fully Disabled"
             } else {
                 "Custom:"
@@ -131,110 +100,108 @@ fn show_sip_bits(ui: &mut egui::Ui, bits: u32, version: &Version) {
         ))
         .size(32.0),
     );
-    ui.label(format!(
-        "CSR_ALLOW_UNTRUSTED_KEXTS (Allow unsigned kernel drivers to be installed and loaded): {}",
-        if (bits & (1 << 0)) != 0 {
-            "Allowed"
-        } else {
-            "Forbidden"
-        }
-    ));
-    ui.label(format!(
-        "CSR_ALLOW_UNRESTRICTED_FS (Allows unrestricted filesystem access): {}",
-        if (bits & (1 << 1)) != 0 {
-            "Allowed"
-        } else {
-            "Forbidden"
+    for flag in csr::flags_for(version) {
+        ui.label(format!(
+            "{} ({}): {}",
+            flag.name,
+            flag.description,
+            if flag.is_set(bits) {
+                "Allowed"
+            } else {
+                "Forbidden"
+            }
+        ));
+    }
+    for bit in csr::unrecognized_bits(bits, version) {
+        ui.label(format!("Unrecognized bit {bit}: Set"));
+    }
+}
+
+fn show_source_warnings(ui: &mut egui::Ui, data: &SIPData, bits: u32) {
+    match &data.nvram_bits {
+        Some(Ok(nvram)) => {
+            // An unset variable means SIP is fully enabled on Intel. Apple Silicon keeps SIP in the LocalPolicy instead
+            let nvram = if cfg!(target_arch = "x86_64") {
+                Some(nvram.unwrap_or(0))
+            } else {
+                *nvram
+            };
+            if let Some(nvram) = nvram
+                && nvram != bits
+            {
+                ui.label(
+                    RichText::new(format!(
+                        "Warning: csr-active-config in NVRAM (0x{nvram:X}) differs from the active configuration (0x{bits:X}). It will apply on next boot."
+                    ))
+                    .color(egui::Color32::ORANGE),
+                );
+            }
         }
-    ));
-    ui.label(format!(
-        "CSR_ALLOW_TASK_FOR_PID (Alows tracking processes based off of a provided process ID): {}",
-        if (bits & (1 << 2)) != 0 {
-            "Allowed"
-        } else {
-            "Forbidden"
+        Some(Err(e)) => {
+            ui.label(format!("Could not read csr-active-config from NVRAM: {e}"));
         }
-    ));
-    ui.label(
-        format!(
-            "CSR_ALLOW_KERNEL_DEBUGGER (Allows attacking a low level kernel debugger to the system): {}",
-            if (bits & (1 << 3)) != 0 {"Allowed"} else {"Forbidden"}
-        )
-    );
-    ui.label(
-        format!(
-            "CSR_ALLOW_APPLE_INTERNAL (Allows apple internal feature set (primarily for development devices)): {}",
-            if (bits & (1 << 4)) != 0 {"Allowed"} else {"Forbidden"}
-        )
-    );
-    ui.label(format!(
-        "CSR_ALLOW_UNRESTRICTED_DTRACE (Allows unrestricted dtrace usage): {}",
-        if (bits & (1 << 5)) != 0 {
-            "Allowed"
-        } else {
-            "Forbidden"
+        None => {}
+    }
+
+    match &data.csrutil_status {
+        Some(Ok(status)) if !status.matches(bits) => {
+            ui.label(
+                RichText::new(format!(
+                    "Warning: csrutil status (0x{:X}) disagrees with the active configuration (0x{:X}).",
+                    status.bits,
+                    bits & status.known_mask
+                ))
+                .color(egui::Color32::ORANGE),
+            );
         }
-    ));
-    ui.label(format!(
-        "CSR_ALLOW_UNRESTRICTED_NVRAM (Allows unrestricted NVRAM write): {}",
-        if (bits & (1 << 6)) != 0 {
-            "Allowed"
-        } else {
-            "Forbidden"
+        Some(Err(e)) => {
+            ui.label(format!("Could not read csrutil status: {e}"));
         }
-    ));
-    ui.label(
-        format!(
-            "CSR_ALLOW_DEVICE_CONFIGURATION (Allows custom device trees (based off of speculation. There is little public info on what this bit does)): {}",
-            if (bits & (1 << 7)) != 0 {"Allowed"} else {"Forbidden"}
-        )
-    );
-    // Those were all the EL Capitan bits
-    if version < &sierra {
-        return;
+        _ => {}
     }
-    ui.label(
-        format!(
-            "CSR_ALLOW_ANY_RECOVERY_OS (Skip BaseSystem Verification, primarily for custom recoveryOS images): {}",
-            if (bits & (1 << 8)) != 0 {"Allowed"} else {"Forbidden"}
-        )
-    );
-    // Only 1 bit was added in Sierra
-    if version < &high_sierra {
-        return;
-    }
-    ui.label(format!(
-        "CSR_ALLOW_UNAPPROVED_KEXTS (Allows unapproved kernel driver installation/loading): {}",
-        if (bits & (1 << 9)) != 0 {
-            "Allowed"
-        } else {
-            "Forbidden"
+}
+
+fn show_csrutil_generator(ui: &mut egui::Ui, desired: &mut u32, version: &Version) {
+    ui.label("SIP can only be changed from Recovery. Pick the flags you want and run the commands below there.");
+
+    for flag in csr::flags_for(version).filter(|flag| flag.user_settable) {
+        let mut allowed = flag.is_set(*desired);
+        if ui.checkbox(&mut allowed, flag.name).changed() {
+            *desired ^= flag.mask();
         }
-    ));
-    // Same for High Sierra
-    if version < &mojave {
-        return;
     }
-    ui.label(format!(
-        "CSR_ALLOW_EXECUTABLE_POLICY_OVERRIDE (Allows override of executable policy): {}",
-        if (bits & (1 << 10)) != 0 {
-            "Allowed"
-        } else {
-            "Forbidden"
-        }
-    ));
-    // Same for Mojave
-    if version < &big_sur {
-        return;
+
+    ui.add_space(10.0);
+
+    let commands = csrutil::csrutil_commands(*desired, version);
+    for command in &commands.commands {
+        ui.horizontal(|ui| {
+            ui.monospace(command);
+            if ui.button("Copy").clicked() {
+                ui.ctx().copy_text(command.clone());
+            }
+        });
     }
-    ui.label(format!(
-        "CSR_ALLOW_UNAUTHENTICATED_ROOT (Allows custom APFS snapshots to be booted): {}",
-        if (bits & (1 << 11)) != 0 {
-            "Allowed"
-        } else {
-            "Forbidden"
+    if !commands.is_exact(*desired) {
+        ui.label(
+            RichText::new(format!(
+                "Warning: This combination can't be expressed with csrutil switches. The commands above result in 0x{:X} instead of 0x{:X}. Use csr-active-config below instead.",
+                commands.result, *desired
+            ))
+            .color(egui::Color32::ORANGE),
+        );
+    }
+
+    ui.add_space(10.0);
+
+    let data = csrutil::config_plist_data(*desired);
+    ui.horizontal(|ui| {
+        ui.label(format!("csr-active-config (0x{:X}):", *desired));
+        ui.monospace(&data);
+        if ui.button("Copy").clicked() {
+            ui.ctx().copy_text(data.clone());
         }
-    ));
+    });
 }
 
 pub fn main(app: &mut MyApp, ctx: &egui::Context) {
@@ -267,8 +234,28 @@ pub fn main(app: &mut MyApp, ctx: &egui::Context) {
             }
         }
 
+        if app.sip_data.nvram_bits.is_none() {
+            app.sip_data.nvram_bits = Some(sources::get_nvram_csr());
+        }
+        if app.sip_data.csrutil_status.is_none() {
+            app.sip_data.csrutil_status = Some(sources::get_csrutil_status());
+        }
+
         if let Some(bits) = app.sip_data.bits {
-            show_sip_bits(ui, bits, version);
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                show_source_warnings(ui, &app.sip_data, bits);
+                show_sip_bits(ui, bits, version);
+
+                ui.add_space(20.0);
+
+                let desired = app
+                    .sip_data
+                    .desired
+                    .get_or_insert(bits & csr::disabled_mask(version));
+                ui.collapsing("Generate csrutil command", |ui| {
+                    show_csrutil_generator(ui, desired, version);
+                });
+            });
         }
     });
 }
@@ -0,0 +1,291 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use os_info::Version;
+
+/// A single CSR (SIP) configuration flag, as defined in xnu's `bsd/sys/csr.h`
+pub struct CsrFlag {
+    pub name: &'static str,
+    pub bit: u8,
+    pub description: &'static str,
+    // The first macOS version that knows about this flag
+    pub introduced: Version,
+    // Whether the flag can be set on a retail machine (and so is offered by the generator)
+    pub user_settable: bool,
+    // Whether the flag has to be set for SIP to count as "Fully Disabled"
+    pub disables_sip: bool,
+}
+
+impl CsrFlag {
+    pub const fn mask(&self) -> u32 {
+        1 << self.bit
+    }
+
+    pub fn is_set(&self, bits: u32) -> bool {
+        (bits & self.mask()) != 0
+    }
+
+    pub fn available_on(&self, version: &Version) -> bool {
+        version >= &self.introduced
+    }
+}
+
+/// Every known CSR flag, in bit order
+pub const CSR_FLAGS: &[CsrFlag] = &[
+    // El Capitan
+    CsrFlag {
+        name: "CSR_ALLOW_UNTRUSTED_KEXTS",
+        bit: 0,
+        description: "Allow unsigned kernel drivers to be installed and loaded",
+        introduced: Version::Semantic(10, 11, 0),
+        user_settable: true,
+        disables_sip: true,
+    },
+    CsrFlag {
+        name: "CSR_ALLOW_UNRESTRICTED_FS",
+        bit: 1,
+        description: "Allows unrestricted filesystem access",
+        introduced: Version::Semantic(10, 11, 0),
+        user_settable: true,
+        disables_sip: true,
+    },
+    CsrFlag {
+        name: "CSR_ALLOW_TASK_FOR_PID",
+        bit: 2,
+        description: "Alows tracking processes based off of a provided process ID",
+        introduced: Version::Semantic(10, 11, 0),
+        user_settable: true,
+        disables_sip: true,
+    },
+    CsrFlag {
+        name: "CSR_ALLOW_KERNEL_DEBUGGER",
+        bit: 3,
+        description: "Allows attacking a low level kernel debugger to the system",
+        introduced: Version::Semantic(10, 11, 0),
+        user_settable: true,
+        disables_sip: true,
+    },
+    // Can't be set outside of Apple, so it's left out of "Fully Disabled"
+    CsrFlag {
+        name: "CSR_ALLOW_APPLE_INTERNAL",
+        bit: 4,
+        description: "Allows apple internal feature set (primarily for development devices)",
+        introduced: Version::Semantic(10, 11, 0),
+        user_settable: false,
+        disables_sip: false,
+    },
+    CsrFlag {
+        name: "CSR_ALLOW_UNRESTRICTED_DTRACE",
+        bit: 5,
+        description: "Allows unrestricted dtrace usage",
+        introduced: Version::Semantic(10, 11, 0),
+        user_settable: true,
+        disables_sip: true,
+    },
+    CsrFlag {
+        name: "CSR_ALLOW_UNRESTRICTED_NVRAM",
+        bit: 6,
+        description: "Allows unrestricted NVRAM write",
+        introduced: Version::Semantic(10, 11, 0),
+        user_settable: true,
+        disables_sip: true,
+    },
+    CsrFlag {
+        name: "CSR_ALLOW_DEVICE_CONFIGURATION",
+        bit: 7,
+        description: "Allows custom device trees (based off of speculation. There is little public info on what this bit does)",
+        introduced: Version::Semantic(10, 11, 0),
+        user_settable: true,
+        disables_sip: true,
+    },
+    // Sierra
+    CsrFlag {
+        name: "CSR_ALLOW_ANY_RECOVERY_OS",
+        bit: 8,
+        description: "Skip BaseSystem Verification, primarily for custom recoveryOS images",
+        introduced: Version::Semantic(10, 12, 0),
+        user_settable: true,
+        disables_sip: true,
+    },
+    // High Sierra
+    CsrFlag {
+        name: "CSR_ALLOW_UNAPPROVED_KEXTS",
+        bit: 9,
+        description: "Allows unapproved kernel driver installation/loading",
+        introduced: Version::Semantic(10, 13, 0),
+        user_settable: true,
+        disables_sip: true,
+    },
+    // Mojave
+    CsrFlag {
+        name: "CSR_ALLOW_EXECUTABLE_POLICY_OVERRIDE",
+        bit: 10,
+        description: "Allows override of executable policy",
+        introduced: Version::Semantic(10, 14, 0),
+        user_settable: true,
+        disables_sip: true,
+    },
+    // Big Sur
+    CsrFlag {
+        name: "CSR_ALLOW_UNAUTHENTICATED_ROOT",
+        bit: 11,
+        description: "Allows custom APFS snapshots to be booted",
+        introduced: Version::Semantic(11, 0, 0),
+        user_settable: true,
+        disables_sip: true,
+    },
+    // Monterey
+    // Only needed for Security Research VMs, so `csrutil disable` and the usual fully disabled
+    // value (0xFEF) leave it unset
+    CsrFlag {
+        name: "CSR_ALLOW_RESEARCH_GUESTS",
+        bit: 12,
+        description: "Allows booting research guest VMs (Security Research VMs)",
+        introduced: Version::Semantic(12, 0, 0),
+        user_settable: true,
+        disables_sip: false,
+    },
+];
+
+/// What `csrutil disable` sets (CSR_DISABLE_FLAGS in xnu)
+pub const CSR_DISABLE_FLAGS: &[&str] = &[
+    "CSR_ALLOW_UNTRUSTED_KEXTS",
+    "CSR_ALLOW_UNRESTRICTED_FS",
+    "CSR_ALLOW_TASK_FOR_PID",
+    "CSR_ALLOW_KERNEL_DEBUGGER",
+    "CSR_ALLOW_APPLE_INTERNAL",
+    "CSR_ALLOW_UNRESTRICTED_DTRACE",
+    "CSR_ALLOW_UNRESTRICTED_NVRAM",
+];
+
+/// Looks up a flag by its xnu name
+pub fn find(name: &str) -> Option<&'static CsrFlag> {
+    CSR_FLAGS.iter().find(|flag| flag.name == name)
+}
+
+/// The combined mask of the named flags
+pub fn mask_of(names: &[&str]) -> u32 {
+    names
+        .iter()
+        .filter_map(|name| find(name))
+        .fold(0, |mask, flag| mask | flag.mask())
+}
+
+/// The flags that exist on the given macOS version
+pub fn flags_for(version: &Version) -> impl Iterator<Item = &'static CsrFlag> {
+    CSR_FLAGS
+        .iter()
+        .filter(move |flag| flag.available_on(version))
+}
+
+/// Set bits that don't belong to any flag known on the given macOS version
+pub fn unrecognized_bits(bits: u32, version: &Version) -> impl Iterator<Item = u8> {
+    let known = flags_for(version).fold(0, |mask, flag| mask | flag.mask());
+    (0..u32::BITS as u8).filter(move |bit| (bits & !known & (1 << bit)) != 0)
+}
+
+/// The bits that have to be set for SIP to be fully disabled on the given macOS version
+pub fn disabled_mask(version: &Version) -> u32 {
+    flags_for(version)
+        .filter(|flag| flag.disables_sip)
+        .fold(0, |mask, flag| mask | flag.mask())
+}
+
+pub fn is_sip_disabled(bits: u32, version: &Version) -> bool {
+    let mask = disabled_mask(version);
+    (bits & mask) == mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EL_CAPITAN: Version = Version::Semantic(10, 11, 0);
+    const SIERRA: Version = Version::Semantic(10, 12, 0);
+    const HIGH_SIERRA: Version = Version::Semantic(10, 13, 0);
+    const MOJAVE: Version = Version::Semantic(10, 14, 0);
+    const BIG_SUR: Version = Version::Semantic(11, 0, 0);
+    const MONTEREY: Version = Version::Semantic(12, 0, 0);
+
+    #[test]
+    fn flags_for_each_generation() {
+        let names = |version| {
+            flags_for(&version)
+                .map(|flag| flag.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(EL_CAPITAN).len(), 8);
+        assert_eq!(names(SIERRA).last(), Some(&"CSR_ALLOW_ANY_RECOVERY_OS"));
+        assert_eq!(
+            names(HIGH_SIERRA).last(),
+            Some(&"CSR_ALLOW_UNAPPROVED_KEXTS")
+        );
+        assert_eq!(
+            names(MOJAVE).last(),
+            Some(&"CSR_ALLOW_EXECUTABLE_POLICY_OVERRIDE")
+        );
+        assert_eq!(
+            names(BIG_SUR).last(),
+            Some(&"CSR_ALLOW_UNAUTHENTICATED_ROOT")
+        );
+        assert_eq!(names(BIG_SUR).len(), 12);
+    }
+
+    #[test]
+    fn disabled_mask_for_each_generation() {
+        // Everything known except CSR_ALLOW_APPLE_INTERNAL
+        assert_eq!(disabled_mask(&EL_CAPITAN), 0xEF);
+        assert_eq!(disabled_mask(&SIERRA), 0x1EF);
+        assert_eq!(disabled_mask(&HIGH_SIERRA), 0x3EF);
+        assert_eq!(disabled_mask(&MOJAVE), 0x7EF);
+        assert_eq!(disabled_mask(&BIG_SUR), 0xFEF);
+    }
+
+    #[test]
+    fn is_sip_disabled_for_each_generation() {
+        for version in [EL_CAPITAN, SIERRA, HIGH_SIERRA, MOJAVE, BIG_SUR] {
+            let mask = disabled_mask(&version);
+            assert!(!is_sip_disabled(0, &version));
+            assert!(is_sip_disabled(mask, &version));
+            // CSR_ALLOW_APPLE_INTERNAL doesn't matter either way
+            assert!(is_sip_disabled(mask | 0x10, &version));
+            // Missing the newest flag of that generation
+            let newest = flags_for(&version).last().unwrap().mask();
+            assert!(!is_sip_disabled(mask & !newest, &version));
+        }
+    }
+
+    #[test]
+    fn high_sierra_needs_unapproved_kexts() {
+        // The old check (`!(bits & 512) == 0`) was never true, so this counted as disabled
+        assert!(!is_sip_disabled(0x1EF, &HIGH_SIERRA));
+        assert!(is_sip_disabled(0x3EF, &HIGH_SIERRA));
+        // Newer bits don't count on older versions
+        assert!(is_sip_disabled(0x1EF, &SIERRA));
+    }
+
+    #[test]
+    fn research_guests_not_needed_to_disable() {
+        assert_eq!(disabled_mask(&MONTEREY), 0xFEF);
+        assert!(is_sip_disabled(0xFEF, &MONTEREY));
+        assert!(is_sip_disabled(0x1FEF, &MONTEREY));
+        // Still offered by the generator
+        assert!(find("CSR_ALLOW_RESEARCH_GUESTS").unwrap().user_settable);
+    }
+}
@@ -0,0 +1,242 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use super::csr;
+use os_info::Version;
+
+/// A `csrutil enable --without <name>` switch and the flags it sets
+struct CsrutilSwitch {
+    name: &'static str,
+    flags: &'static [&'static str],
+    introduced: Version,
+}
+
+const SWITCHES: &[CsrutilSwitch] = &[
+    CsrutilSwitch {
+        name: "kext",
+        flags: &["CSR_ALLOW_UNTRUSTED_KEXTS"],
+        introduced: Version::Semantic(10, 11, 0),
+    },
+    CsrutilSwitch {
+        name: "fs",
+        flags: &["CSR_ALLOW_UNRESTRICTED_FS"],
+        introduced: Version::Semantic(10, 11, 0),
+    },
+    CsrutilSwitch {
+        name: "debug",
+        flags: &["CSR_ALLOW_TASK_FOR_PID", "CSR_ALLOW_KERNEL_DEBUGGER"],
+        introduced: Version::Semantic(10, 11, 0),
+    },
+    CsrutilSwitch {
+        name: "dtrace",
+        flags: &["CSR_ALLOW_UNRESTRICTED_DTRACE"],
+        introduced: Version::Semantic(10, 11, 0),
+    },
+    CsrutilSwitch {
+        name: "nvram",
+        flags: &["CSR_ALLOW_UNRESTRICTED_NVRAM"],
+        introduced: Version::Semantic(10, 11, 0),
+    },
+    CsrutilSwitch {
+        name: "basesystem",
+        flags: &["CSR_ALLOW_ANY_RECOVERY_OS"],
+        introduced: Version::Semantic(10, 12, 0),
+    },
+];
+
+pub struct CsrutilCommands {
+    // The commands to run in Recovery, in order
+    pub commands: Vec<String>,
+    // The bits the commands will actually produce
+    pub result: u32,
+}
+
+impl CsrutilCommands {
+    /// Whether running the commands results in exactly the requested bits
+    pub fn is_exact(&self, bits: u32) -> bool {
+        self.result == bits
+    }
+}
+
+/// Builds the closest `csrutil` invocation(s) for the requested bits.
+/// Check `is_exact` to find out if the bits can't be expressed with `csrutil` switches.
+pub fn csrutil_commands(bits: u32, version: &Version) -> CsrutilCommands {
+    let unauthenticated_root = csr::mask_of(&["CSR_ALLOW_UNAUTHENTICATED_ROOT"]);
+    // CSR_ALLOW_APPLE_INTERNAL is never asked for, so leave it out when comparing
+    let disable =
+        csr::mask_of(csr::CSR_DISABLE_FLAGS) & !csr::mask_of(&["CSR_ALLOW_APPLE_INTERNAL"]);
+
+    let mut commands = Vec::new();
+    let mut result = 0;
+
+    let base = bits & !unauthenticated_root;
+    if base == 0 {
+        commands.push("csrutil enable".to_string());
+    } else if base == disable {
+        commands.push("csrutil disable".to_string());
+        result |= disable;
+    } else {
+        let mut command = String::from("csrutil enable");
+        for switch in SWITCHES
+            .iter()
+            .filter(|switch| version >= &switch.introduced)
+        {
+            let mask = csr::mask_of(switch.flags);
+            // Only use a switch when every flag it sets was asked for
+            if (base & mask) == mask {
+                command.push_str(" --without ");
+                command.push_str(switch.name);
+                result |= mask;
+            }
+        }
+        commands.push(command);
+    }
+
+    if version >= &Version::Semantic(11, 0, 0) {
+        if (bits & unauthenticated_root) != 0 {
+            commands.push("csrutil authenticated-root disable".to_string());
+            result |= unauthenticated_root;
+        } else {
+            commands.push("csrutil authenticated-root enable".to_string());
+        }
+    }
+
+    CsrutilCommands { commands, result }
+}
+
+/// The `csr-active-config` value for an OpenCore/Clover `config.plist` (4 bytes, little-endian)
+pub fn config_plist_data(bits: u32) -> String {
+    bits.to_le_bytes()
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EL_CAPITAN: Version = Version::Semantic(10, 11, 0);
+    const SIERRA: Version = Version::Semantic(10, 12, 0);
+    const CATALINA: Version = Version::Semantic(10, 15, 0);
+    const BIG_SUR: Version = Version::Semantic(11, 0, 0);
+
+    // What `csrutil disable` sets, without CSR_ALLOW_APPLE_INTERNAL
+    const DISABLED: u32 = 0x6F;
+    const UNAUTHENTICATED_ROOT: u32 = 0x800;
+
+    #[test]
+    fn fully_enabled() {
+        let commands = csrutil_commands(0, &CATALINA);
+        assert_eq!(commands.commands, ["csrutil enable"]);
+        assert!(commands.is_exact(0));
+
+        let commands = csrutil_commands(0, &BIG_SUR);
+        assert_eq!(
+            commands.commands,
+            ["csrutil enable", "csrutil authenticated-root enable"]
+        );
+        assert!(commands.is_exact(0));
+    }
+
+    #[test]
+    fn fully_disabled() {
+        let commands = csrutil_commands(DISABLED, &CATALINA);
+        assert_eq!(commands.commands, ["csrutil disable"]);
+        assert!(commands.is_exact(DISABLED));
+
+        let bits = DISABLED | UNAUTHENTICATED_ROOT;
+        let commands = csrutil_commands(bits, &BIG_SUR);
+        assert_eq!(
+            commands.commands,
+            ["csrutil disable", "csrutil authenticated-root disable"]
+        );
+        assert!(commands.is_exact(bits));
+    }
+
+    #[test]
+    fn each_switch_on_each_version() {
+        for version in [EL_CAPITAN, SIERRA, CATALINA, BIG_SUR] {
+            for switch in SWITCHES {
+                let bits = csr::mask_of(switch.flags);
+                let commands = csrutil_commands(bits, &version);
+
+                if version >= switch.introduced {
+                    assert_eq!(
+                        commands.commands[0],
+                        format!("csrutil enable --without {}", switch.name)
+                    );
+                    assert!(commands.is_exact(bits), "{} on {version}", switch.name);
+                } else {
+                    assert_eq!(commands.commands[0], "csrutil enable");
+                    assert!(!commands.is_exact(bits), "{} on {version}", switch.name);
+                }
+
+                let expected = if version >= BIG_SUR { 2 } else { 1 };
+                assert_eq!(commands.commands.len(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn switches_are_combined() {
+        let commands = csrutil_commands(0x3, &SIERRA);
+        assert_eq!(
+            commands.commands,
+            ["csrutil enable --without kext --without fs"]
+        );
+        assert!(commands.is_exact(0x3));
+    }
+
+    #[test]
+    fn not_exact() {
+        // `--without debug` sets CSR_ALLOW_KERNEL_DEBUGGER too
+        let commands = csrutil_commands(0x4, &CATALINA);
+        assert_eq!(commands.commands, ["csrutil enable"]);
+        assert_eq!(commands.result, 0);
+        assert!(!commands.is_exact(0x4));
+
+        // CSR_ALLOW_UNAPPROVED_KEXTS has no switch
+        let commands = csrutil_commands(0x1 | 0x200, &CATALINA);
+        assert_eq!(commands.commands, ["csrutil enable --without kext"]);
+        assert_eq!(commands.result, 0x1);
+        assert!(!commands.is_exact(0x1 | 0x200));
+    }
+
+    #[test]
+    fn authenticated_root() {
+        let commands = csrutil_commands(UNAUTHENTICATED_ROOT, &BIG_SUR);
+        assert_eq!(
+            commands.commands,
+            ["csrutil enable", "csrutil authenticated-root disable"]
+        );
+        assert!(commands.is_exact(UNAUTHENTICATED_ROOT));
+
+        // Catalina has no authenticated root, so there's no command for it
+        let commands = csrutil_commands(UNAUTHENTICATED_ROOT, &CATALINA);
+        assert_eq!(commands.commands, ["csrutil enable"]);
+        assert!(!commands.is_exact(UNAUTHENTICATED_ROOT));
+    }
+
+    #[test]
+    fn config_plist_data_is_little_endian() {
+        assert_eq!(config_plist_data(0), "00000000");
+        assert_eq!(config_plist_data(0x67), "67000000");
+        assert_eq!(config_plist_data(0xFEF), "EF0F0000");
+    }
+}
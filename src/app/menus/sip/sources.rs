@@ -0,0 +1,228 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Other places the SIP configuration can be read from, besides csr_get_active_config
+
+use super::csr;
+use crate::app::helpers::decode_nvram_value;
+use crate::command_output;
+
+// The lines in the "Configuration:" section of `csrutil status` and the flags they describe
+const STATUS_LABELS: &[(&str, &[&str])] = &[
+    ("Apple Internal", &["CSR_ALLOW_APPLE_INTERNAL"]),
+    ("Kext Signing", &["CSR_ALLOW_UNTRUSTED_KEXTS"]),
+    ("Filesystem Protections", &["CSR_ALLOW_UNRESTRICTED_FS"]),
+    (
+        "Debugging Restrictions",
+        &["CSR_ALLOW_TASK_FOR_PID", "CSR_ALLOW_KERNEL_DEBUGGER"],
+    ),
+    ("DTrace Restrictions", &["CSR_ALLOW_UNRESTRICTED_DTRACE"]),
+    ("NVRAM Protections", &["CSR_ALLOW_UNRESTRICTED_NVRAM"]),
+    ("BaseSystem Verification", &["CSR_ALLOW_ANY_RECOVERY_OS"]),
+];
+
+/// What `csrutil status` reported
+pub struct CsrutilStatus {
+    pub bits: u32,
+    // The bits the status text actually says something about
+    pub known_mask: u32,
+}
+
+impl CsrutilStatus {
+    /// Whether the status agrees with a full bit configuration
+    pub fn matches(&self, bits: u32) -> bool {
+        (bits & self.known_mask) == self.bits
+    }
+}
+
+/// Parses the output of `nvram csr-active-config`.
+/// Returns `None` when the variable isn't set.
+pub fn parse_nvram_csr(output: &str) -> Result<Option<u32>, String> {
+    let output = output.trim_end_matches('\n');
+    if output.is_empty() {
+        return Ok(None);
+    }
+
+    let value = match output.split_once('\t') {
+        Some(("csr-active-config", value)) => value,
+        _ => return Err(format!("Unexpected nvram output: {output:?}")),
+    };
+
+    let bytes = decode_nvram_value(value)?;
+    if bytes.len() > 4 {
+        return Err(format!("csr-active-config is {} bytes long", bytes.len()));
+    }
+
+    // Little-endian
+    Ok(Some(
+        bytes
+            .iter()
+            .rev()
+            .fold(0, |bits, byte| (bits << 8) | *byte as u32),
+    ))
+}
+
+/// Parses the output of `csrutil status`
+pub fn parse_csrutil_status(output: &str) -> Result<CsrutilStatus, String> {
+    let summary = output
+        .lines()
+        .find_map(|line| line.strip_prefix("System Integrity Protection status: "))
+        .ok_or_else(|| format!("Unexpected csrutil output: {output:?}"))?;
+
+    let all_labels = STATUS_LABELS
+        .iter()
+        .fold(0, |mask, (_, flags)| mask | csr::mask_of(flags));
+
+    if summary.starts_with("enabled.") {
+        return Ok(CsrutilStatus {
+            bits: 0,
+            known_mask: all_labels,
+        });
+    }
+    if summary.starts_with("disabled.") {
+        return Ok(CsrutilStatus {
+            bits: csr::mask_of(csr::CSR_DISABLE_FLAGS),
+            known_mask: all_labels,
+        });
+    }
+
+    // Custom configuration. Each line says whether the protection is enabled or disabled
+    let mut status = CsrutilStatus {
+        bits: 0,
+        known_mask: 0,
+    };
+    for line in output.lines() {
+        let Some((label, state)) = line.trim().split_once(": ") else {
+            continue;
+        };
+        let Some((_, flags)) = STATUS_LABELS.iter().find(|(name, _)| *name == label) else {
+            continue;
+        };
+        let mask = csr::mask_of(flags);
+
+        status.known_mask |= mask;
+        // "Apple Internal: enabled" means the flag is set, everything else is a protection
+        let set = if label == "Apple Internal" {
+            state == "enabled"
+        } else {
+            state == "disabled"
+        };
+        if set {
+            status.bits |= mask;
+        }
+    }
+
+    if status.known_mask == 0 {
+        return Err(format!("Unexpected csrutil output: {output:?}"));
+    }
+    Ok(status)
+}
+
+/// Reads the SIP configuration that will apply on next boot
+pub fn get_nvram_csr() -> Result<Option<u32>, String> {
+    parse_nvram_csr(&command_output!("nvram", "csr-active-config"))
+}
+
+pub fn get_csrutil_status() -> Result<CsrutilStatus, String> {
+    parse_csrutil_status(&command_output!("csrutil", "status"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CUSTOM_CONFIGURATION: &str = "System Integrity Protection status: unknown (Custom Configuration).
+
+Configuration:
+\tApple Internal: disabled
+\tKext Signing: disabled
+\tFilesystem Protections: disabled
+\tDebugging Restrictions: enabled
+\tDTrace Restrictions: enabled
+\tNVRAM Protections: enabled
+\tBaseSystem Verification: enabled
+\tBoot-arg Restrictions: enabled
+\tKernel Integrity Protections: enabled
+\tAuthenticated Root Requirement: enabled
+
+This is an unsupported configuration, likely to break in the future and leave your machine in an unknown state.
+";
+
+    #[test]
+    fn nvram_unset() {
+        // nvram only complains on stderr
+        assert_eq!(parse_nvram_csr(""), Ok(None));
+    }
+
+    #[test]
+    fn nvram_values_are_little_endian() {
+        // `csrutil disable` on Catalina
+        assert_eq!(
+            parse_nvram_csr("csr-active-config\tw%00%00%00\n"),
+            Ok(Some(0x77))
+        );
+        // `csrutil disable` on Monterey
+        assert_eq!(
+            parse_nvram_csr("csr-active-config\t%ef%0f%00%00\n"),
+            Ok(Some(0xFEF))
+        );
+        // Some bootloaders write a single byte
+        assert_eq!(parse_nvram_csr("csr-active-config\t%03\n"), Ok(Some(0x3)));
+    }
+
+    #[test]
+    fn nvram_malformed() {
+        assert!(parse_nvram_csr("boot-args\t-v\n").is_err());
+        assert!(parse_nvram_csr("csr-active-config\t%00%00%00%00%00\n").is_err());
+        assert!(parse_nvram_csr("csr-active-config\t%0\n").is_err());
+    }
+
+    #[test]
+    fn csrutil_enabled_and_disabled() {
+        let enabled =
+            parse_csrutil_status("System Integrity Protection status: enabled.\n").unwrap();
+        assert_eq!(enabled.bits, 0);
+        assert!(enabled.matches(0));
+        assert!(!enabled.matches(0x2));
+
+        let disabled =
+            parse_csrutil_status("System Integrity Protection status: disabled.\n").unwrap();
+        assert_eq!(disabled.bits, csr::mask_of(csr::CSR_DISABLE_FLAGS));
+        assert!(disabled.matches(0x7F));
+        // Unapproved kexts aren't in the status text
+        assert!(disabled.matches(0x27F));
+        assert!(!disabled.matches(0x3));
+    }
+
+    #[test]
+    fn csrutil_custom_configuration() {
+        let status = parse_csrutil_status(CUSTOM_CONFIGURATION).unwrap();
+        assert_eq!(status.bits, 0x3);
+        assert_eq!(status.known_mask, 0x17F);
+        assert!(status.matches(0x3));
+        assert!(status.matches(0x803));
+        assert!(!status.matches(0x7));
+    }
+
+    #[test]
+    fn csrutil_unexpected_output() {
+        assert!(parse_csrutil_status("").is_err());
+        assert!(parse_csrutil_status("csrutil: command not found\n").is_err());
+        assert!(parse_csrutil_status("System Integrity Protection status: unknown.\n").is_err());
+    }
+}
@@ -16,49 +16,429 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+mod alerts;
+mod devices;
+mod startup;
+mod worker;
+
 use crate::app::{Menu, MyApp};
+use alerts::AlertSettings;
+use devices::{AudioDevice, DeviceRole};
+use startup::StartupChime;
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, channel};
+use worker::{VolumeWorker, WorkerEvent};
 
 use eframe::egui;
 use eframe::egui::RichText;
 
-use crate::{command_output, run_command};
+use crate::app::applescript::{self, Value};
+
+/// Everything `get volume settings` returns. Volumes are percentages
+#[derive(Clone, Copy, PartialEq, Default)]
+pub struct VolumeSettings {
+    pub output: u8,
+    // None when there is no input device
+    pub input: Option<u8>,
+    // Relative to the output volume
+    pub alert: u8,
+    pub muted: bool,
+}
 
 #[derive(Default)]
 pub struct SoundData {
-    // The value of the slider
-    slider_value: f32,
-    // If the value is not out of date (because bool defaults to false)
-    reload_not_needed: bool,
-    // The save volume
-    last_volume: u8,
-}
-
-/// VERY expensive function. Do NOT call unless required
-fn get_volume() -> Result<u8, String> {
-    Ok(
-        command_output!("osascript", "-e", "output volume of (get volume settings)")
-            .strip_suffix("\n")
-            .unwrap()
-            .parse::<u8>()
-            .unwrap(),
-    )
-}
-
-/// VERY expensive function. Do NOT call unless required
-fn set_volume(volume: u8) -> Result<(), String> {
-    run_command!(
-        "osascript",
-        "-e",
-        format!("set volume output volume {volume}")
-    );
+    // The values of the sliders
+    slider: VolumeSettings,
+    // The last values sent to the worker, so only changes are sent
+    sent: Option<VolumeSettings>,
+    // Whether the worker hasn't applied `sent` yet. The sliders aren't synced to the system until it has
+    pending: bool,
+    // The latest settings read from (or applied to) the system
+    last_settings: Option<Result<VolumeSettings, String>>,
+    // Error from the last apply
+    apply_error: Option<String>,
+    // Only runs while the menu is open
+    worker: Option<VolumeWorker>,
+    // Input and output devices
+    devices: Option<Result<Vec<AudioDevice>, String>>,
+    // The devices being listed on a background thread
+    devices_lookup: Option<Receiver<Result<Vec<AudioDevice>, String>>>,
+    // Error from switching devices
+    device_error: Option<String>,
+    // Alert sound and UI sound effects
+    alerts: Option<Result<AlertSettings, String>>,
+    // Every alert sound that can be picked
+    alert_sounds: Option<Result<Vec<PathBuf>, String>>,
+    // Error from saving or previewing alert settings
+    alert_error: Option<String>,
+    startup_chime: Option<Result<StartupChime, String>>,
+    // Error from changing the startup chime
+    startup_error: Option<String>,
+    // A startup chime change waiting for the admin prompt on a background thread
+    startup_change: Option<Receiver<Result<(), String>>>,
+}
+
+fn volume_field(settings: &Value, key: &str) -> Result<Option<u8>, String> {
+    match settings.get(key) {
+        Some(Value::Missing) => Ok(None),
+        Some(value) => match value.as_integer().map(u8::try_from) {
+            Some(Ok(volume)) if volume <= 100 => Ok(Some(volume)),
+            _ => Err(format!("Unexpected {key}: {value}")),
+        },
+        None => Err(format!("No {key} in {settings}")),
+    }
+}
+
+/// Parses the record `get volume settings` returns
+fn parse_volume_settings(settings: &Value) -> Result<VolumeSettings, String> {
+    let (Some(output), Some(alert)) = (
+        volume_field(settings, "output volume")?,
+        volume_field(settings, "alert volume")?,
+    ) else {
+        return Err(format!("Unexpected volume settings: {settings}"));
+    };
+
+    Ok(VolumeSettings {
+        output,
+        input: volume_field(settings, "input volume")?,
+        alert,
+        muted: settings
+            .get("output muted")
+            .and_then(Value::as_bool)
+            .unwrap_or_default(),
+    })
+}
+
+/// VERY expensive function. Only call from the worker
+fn get_volume_settings() -> Result<VolumeSettings, String> {
+    parse_volume_settings(&applescript::run("get volume settings")?)
+}
+
+/// Sets only what differs from `old`, since every part is a separate command.
+/// VERY expensive function. Only call from the worker
+fn set_volume_settings(new: &VolumeSettings, old: &VolumeSettings) -> Result<(), String> {
+    let mut commands = Vec::new();
+    if new.output != old.output {
+        commands.push(format!(
+            "output volume {}",
+            Value::Integer(new.output.into())
+        ));
+    }
+    if let Some(input) = new.input
+        && new.input != old.input
+    {
+        commands.push(format!("input volume {}", Value::Integer(input.into())));
+    }
+    if new.alert != old.alert {
+        commands.push(format!("alert volume {}", Value::Integer(new.alert.into())));
+    }
+    if new.muted != old.muted {
+        commands.push(format!("output muted {}", Value::Boolean(new.muted)));
+    }
+
+    if commands.is_empty() {
+        return Ok(());
+    }
+    applescript::run(&format!("set volume {}", commands.join(" ")))?;
     Ok(())
 }
 
+fn volume_slider(ui: &mut egui::Ui, size: [f32; 2], value: &mut u8, text: &str) {
+    ui.add_sized(size, egui::Slider::new(value, 0..=100).text(text));
+}
+
+fn show_device(
+    ui: &mut egui::Ui,
+    device: &AudioDevice,
+    roles: &[DeviceRole],
+) -> Option<DeviceRole> {
+    let mut clicked = None;
+
+    ui.horizontal(|ui| {
+        ui.label(RichText::new(&device.name).strong());
+        let mut details = vec![device.transport.clone()];
+        if let Some(manufacturer) = &device.manufacturer {
+            details.push(manufacturer.clone());
+        }
+        if let Some(sample_rate) = device.sample_rate {
+            details.push(format!("{} kHz", sample_rate / 1000.0));
+        }
+        ui.label(details.join(", "));
+
+        for role in roles {
+            if device.is_default(*role) {
+                ui.label(format!("(default for {})", role.name()));
+            } else if ui
+                .add_enabled(
+                    device.uid.is_some(),
+                    egui::Button::new(format!("Use for {}", role.name())),
+                )
+                .on_disabled_hover_text("Another device has the same name")
+                .clicked()
+            {
+                clicked = Some(*role);
+            }
+        }
+    });
+
+    clicked
+}
+
+/// Runs `devices::get_audio_devices` on a background thread
+fn lookup_devices(ctx: &egui::Context) -> Receiver<Result<Vec<AudioDevice>, String>> {
+    let (sender, receiver) = channel();
+    let ctx = ctx.clone();
+
+    std::thread::spawn(move || {
+        // Nobody is waiting anymore if this fails
+        let _ = sender.send(devices::get_audio_devices());
+        ctx.request_repaint();
+    });
+
+    receiver
+}
+
+fn show_devices(ui: &mut egui::Ui, data: &mut SoundData) {
+    if data.devices.is_none() && data.devices_lookup.is_none() {
+        data.devices_lookup = Some(lookup_devices(ui.ctx()));
+    }
+    if let Some(lookup) = &data.devices_lookup
+        && let Ok(res) = lookup.try_recv()
+    {
+        data.devices = Some(res);
+        data.devices_lookup = None;
+    }
+
+    let mut switch = None;
+    match &data.devices {
+        None => {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Loading devices...");
+            });
+        }
+        Some(Ok(devices)) => {
+            ui.label(RichText::new("Output:").heading());
+            for device in devices
+                .iter()
+                .filter(|device| device.supports(DeviceRole::Output))
+            {
+                if let Some(role) =
+                    show_device(ui, device, &[DeviceRole::Output, DeviceRole::SystemOutput])
+                {
+                    switch = device.uid.clone().map(|uid| (role, uid));
+                }
+            }
+
+            ui.label(RichText::new("Input:").heading());
+            for device in devices
+                .iter()
+                .filter(|device| device.supports(DeviceRole::Input))
+            {
+                if let Some(role) = show_device(ui, device, &[DeviceRole::Input]) {
+                    switch = device.uid.clone().map(|uid| (role, uid));
+                }
+            }
+        }
+        Some(Err(e)) => {
+            ui.label(format!("Failed to get audio devices: {e}"));
+        }
+    }
+
+    if ui.button("Reload devices").clicked() {
+        data.devices = None;
+        data.devices_lookup = None;
+    }
+
+    if let Some((role, uid)) = switch {
+        data.device_error = devices::set_default_device(role, &uid).err();
+        // Every device has its own volume. A running lookup could be from before the switch
+        data.devices = None;
+        data.devices_lookup = None;
+        data.worker = None;
+    }
+    if let Some(e) = &data.device_error {
+        ui.label(format!("Failed to switch device: {e}"));
+    }
+}
+
+fn show_alerts(ui: &mut egui::Ui, data: &mut SoundData) {
+    if data.alerts.is_none() {
+        data.alerts = Some(AlertSettings::load());
+    }
+    if data.alert_sounds.is_none() {
+        data.alert_sounds = Some(alerts::list_alert_sounds());
+    }
+
+    let settings = match data.alerts.as_mut().unwrap() {
+        Ok(settings) => settings,
+        Err(e) => {
+            ui.label(format!("Failed to read alert settings: {e}"));
+            return;
+        }
+    };
+    let old = settings.clone();
+
+    ui.horizontal(|ui| {
+        ui.label("Alert sound:");
+        egui::ComboBox::from_id_salt("alert_sound")
+            .selected_text(alerts::sound_name(&settings.sound))
+            .show_ui(ui, |ui| match data.alert_sounds.as_ref().unwrap() {
+                Ok(sounds) => {
+                    for sound in sounds {
+                        let path = sound.display().to_string();
+                        let name = alerts::sound_name(&path);
+                        ui.selectable_value(&mut settings.sound, path, name);
+                    }
+                }
+                Err(e) => {
+                    ui.label(format!("Failed to list sounds: {e}"));
+                }
+            });
+        if ui.button("Play").clicked() {
+            data.alert_error = alerts::preview(&settings.sound).err();
+        }
+    });
+    ui.checkbox(&mut settings.ui_sounds, "Play user interface sound effects");
+    ui.checkbox(
+        &mut settings.volume_feedback,
+        "Play feedback when volume is changed",
+    );
+
+    if *settings != old {
+        // Play the new sound, like System Settings does
+        if settings.sound != old.sound {
+            let _ = alerts::preview(&settings.sound);
+        }
+        data.alert_error = settings.save(&old).err();
+        if data.alert_error.is_some() {
+            // Show what is actually set
+            data.alerts = None;
+        }
+    }
+    if let Some(e) = &data.alert_error {
+        ui.label(format!("Failed to change alert settings: {e}"));
+    }
+}
+
+/// Runs `startup::set_startup_chime` on a background thread, it waits for the admin prompt
+fn start_startup_change(plays: bool, ctx: &egui::Context) -> Receiver<Result<(), String>> {
+    let (sender, receiver) = channel();
+    let ctx = ctx.clone();
+
+    std::thread::spawn(move || {
+        // Nobody is waiting anymore if this fails
+        let _ = sender.send(startup::set_startup_chime(plays));
+        ctx.request_repaint();
+    });
+
+    receiver
+}
+
+fn show_startup_chime(ui: &mut egui::Ui, data: &mut SoundData) {
+    if let Some(change) = &data.startup_change
+        && let Ok(res) = change.try_recv()
+    {
+        data.startup_error = res.err();
+        data.startup_chime = None;
+        data.startup_change = None;
+    }
+    if data.startup_chime.is_none() {
+        data.startup_chime = Some(startup::get_startup_chime());
+    }
+
+    match data.startup_chime.as_ref().unwrap() {
+        Ok(chime) => {
+            let mut plays = chime.plays();
+            ui.label(chime.explanation());
+            let waiting = data.startup_change.is_some();
+            if ui
+                .add_enabled(
+                    !waiting,
+                    egui::Checkbox::new(&mut plays, "Play sound on startup"),
+                )
+                .changed()
+            {
+                data.startup_error = None;
+                data.startup_change = Some(start_startup_change(plays, ui.ctx()));
+            }
+            if waiting {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Waiting for authorization...");
+                });
+            }
+            ui.label("Only Apple Silicon Macs and Intel Macs with a T2 chip on macOS 11 or later use this.");
+        }
+        Err(e) => {
+            ui.label(format!("Failed to read the startup chime setting: {e}"));
+        }
+    }
+
+    if let Some(e) = &data.startup_error {
+        ui.label(format!("Failed to change the startup chime: {e}"));
+    }
+}
+
+impl SoundData {
+    /// Stops polling the volume. It restarts the next time the menu is shown
+    pub fn stop_worker(&mut self) {
+        self.worker = None;
+    }
+
+    /// Handles what the worker sent since the last frame
+    fn receive_events(&mut self) {
+        let Some(worker) = &self.worker else {
+            return;
+        };
+
+        for event in worker.events() {
+            match event {
+                WorkerEvent::Polled(Ok(settings)) => {
+                    // Don't yank the sliders back while a change is on its way
+                    if !self.pending {
+                        self.slider = settings;
+                        self.sent = Some(settings);
+                    }
+                    self.last_settings = Some(Ok(settings));
+                }
+                WorkerEvent::Applied(Ok(settings)) => {
+                    if self.sent == Some(settings) {
+                        self.pending = false;
+                    }
+                    self.apply_error = None;
+                    self.last_settings = Some(Ok(settings));
+                }
+                WorkerEvent::Applied(Err(e)) => {
+                    self.pending = false;
+                    self.apply_error = Some(e);
+                }
+                WorkerEvent::Polled(Err(e)) => {
+                    self.last_settings = Some(Err(e));
+                }
+            }
+        }
+    }
+
+    /// Sends the sliders to the worker if they moved
+    fn send_changes(&mut self) {
+        if let (Some(worker), Some(sent)) = (&self.worker, self.sent)
+            && sent != self.slider
+        {
+            worker.apply(self.slider);
+            self.sent = Some(self.slider);
+            self.pending = true;
+        }
+    }
+}
+
 pub fn main(app: &mut MyApp, ctx: &egui::Context) {
-    if !app.sound_data.reload_not_needed {
-        app.sound_data.last_volume = get_volume().unwrap();
-        app.sound_data.reload_not_needed = true;
+    if app.sound_data.worker.is_none() {
+        app.sound_data.worker = Some(VolumeWorker::start(ctx));
+        app.sound_data.sent = None;
+        app.sound_data.pending = false;
     }
+    app.sound_data.receive_events();
 
     egui::CentralPanel::default().show(ctx, |ui| {
         let spacing = &ui.style().spacing;
@@ -66,27 +446,113 @@ pub fn main(app: &mut MyApp, ctx: &egui::Context) {
 
         if ui.button(RichText::new("Back")).clicked() {
             app.selected_menu = Menu::Main;
+            // No need to keep polling
+            app.sound_data.stop_worker();
         }
         ui.with_layout(egui::Layout::top_down(egui::Align::Center), |ui| {
             ui.label(RichText::new("Sound Menu:").size(36.0));
 
+            let last = match &app.sound_data.last_settings {
+                Some(Ok(last)) => *last,
+                Some(Err(e)) => {
+                    ui.label(format!("Failed to get volume settings: {e}"));
+                    if ui.button("Reload").clicked() {
+                        app.sound_data.worker = None;
+                    }
+                    return;
+                }
+                None => {
+                    ui.spinner();
+                    return;
+                }
+            };
+
             ui.label(format!(
-                "The volume is currently: {}%",
-                app.sound_data.last_volume
+                "The volume is currently: {}%{}",
+                last.output,
+                if last.muted { " (muted)" } else { "" }
             ));
-            if ui.button("Reload").clicked() {
-                app.sound_data.reload_not_needed = false;
-            }
+            match last.input {
+                Some(input) => ui.label(format!("Input volume: {input}%")),
+                None => ui.label("No input device"),
+            };
+            ui.label(format!("Alert volume: {}%", last.alert));
 
-            ui.add_sized(
-                size,
-                egui::Slider::new(&mut app.sound_data.slider_value, 0.0..=100.0).text("New Volume"),
-            );
+            let slider = &mut app.sound_data.slider;
+            volume_slider(ui, size, &mut slider.output, "Output Volume");
+            if let Some(input) = &mut slider.input {
+                volume_slider(ui, size, input, "Input Volume");
+            }
+            volume_slider(ui, size, &mut slider.alert, "Alert Volume");
+            ui.checkbox(&mut slider.muted, "Mute");
+            app.sound_data.send_changes();
 
-            if ui.button("Apply").clicked() {
-                set_volume(app.sound_data.slider_value as u8).unwrap();
-                app.sound_data.reload_not_needed = false;
+            if let Some(e) = &app.sound_data.apply_error {
+                ui.label(format!("Failed to set volume: {e}"));
             }
+
+            ui.collapsing("Devices", |ui| {
+                show_devices(ui, &mut app.sound_data);
+            });
+
+            ui.collapsing("Alerts", |ui| {
+                show_alerts(ui, &mut app.sound_data);
+            });
+
+            ui.collapsing("Startup chime", |ui| {
+                show_startup_chime(ui, &mut app.sound_data);
+            });
         });
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(output: &str) -> Result<VolumeSettings, String> {
+        parse_volume_settings(&applescript::parse(output).unwrap())
+    }
+
+    #[test]
+    fn parses_volume_settings() {
+        let settings =
+            parse("{output volume:56, input volume:75, alert volume:100, output muted:false}")
+                .unwrap();
+        assert_eq!(settings.output, 56);
+        assert_eq!(settings.input, Some(75));
+        assert_eq!(settings.alert, 100);
+        assert!(!settings.muted);
+
+        let muted =
+            parse("{output volume:0, input volume:0, alert volume:0, output muted:true}").unwrap();
+        assert!(muted.muted);
+    }
+
+    #[test]
+    fn no_input_device() {
+        let settings = parse(
+            "{output volume:56, input volume:missing value, alert volume:100, output muted:false}",
+        )
+        .unwrap();
+        assert_eq!(settings.input, None);
+    }
+
+    #[test]
+    fn missing_key() {
+        assert!(parse("{output volume:56, input volume:75, output muted:false}").is_err());
+        assert!(parse("{input volume:75, alert volume:100, output muted:false}").is_err());
+        assert!(parse("{output volume:56, alert volume:100, output muted:false}").is_err());
+    }
+
+    #[test]
+    fn out_of_range() {
+        for volume in ["101", "-1", "300", "\"loud\""] {
+            let output = format!(
+                "{{output volume:{volume}, input volume:75, alert volume:100, output muted:false}}"
+            );
+            assert!(parse(&output).is_err(), "{volume}");
+        }
+        assert!(parse("{output volume:56, input volume:101, alert volume:100}").is_err());
+    }
+}
@@ -0,0 +1,178 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// The alert sound and UI sound effects, which are plain user defaults
+
+use crate::app::helpers::home_dir;
+use log::info;
+use std::path::{Path, PathBuf};
+
+const SYSTEM_SOUNDS: &str = "/System/Library/Sounds";
+const SOUND_EXTENSIONS: &[&str] = &["aiff", "aif", "wav", "caf", "m4a", "mp3"];
+
+const GLOBAL_DOMAIN: &str = "-g";
+const SYSTEM_SOUND_DOMAIN: &str = "com.apple.systemsound";
+// Full path of the alert sound
+const BEEP_SOUND: &str = "com.apple.sound.beep.sound";
+// "Play feedback when volume is changed"
+const BEEP_FEEDBACK: &str = "com.apple.sound.beep.feedback";
+// "Play user interface sound effects"
+const UI_AUDIO: &str = "com.apple.sound.uiaudio.enabled";
+
+// What macOS uses when nothing was picked
+const DEFAULT_ALERT_SOUND: &str = "/System/Library/Sounds/Tink.aiff";
+
+#[derive(Clone, PartialEq)]
+pub struct AlertSettings {
+    pub sound: String,
+    pub ui_sounds: bool,
+    pub volume_feedback: bool,
+}
+
+/// Reads a user default. None if it isn't set
+fn read_default(domain: &str, key: &str) -> Result<Option<String>, String> {
+    let output = std::process::Command::new("defaults")
+        .arg("read")
+        .arg(domain)
+        .arg(key)
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    if !output.status.success() {
+        // defaults fails when the key doesn't exist
+        return Ok(None);
+    }
+    match String::from_utf8(output.stdout) {
+        Ok(value) => Ok(Some(value.trim().to_string())),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// `defaults write domain key <args>`
+fn write_default(domain: &str, key: &str, args: &[&str]) -> Result<(), String> {
+    info!("Setting {domain} {key} to {args:?}");
+
+    let output = std::process::Command::new("defaults")
+        .arg("write")
+        .arg(domain)
+        .arg(key)
+        .args(args)
+        .output()
+        .map_err(|e| e.to_string())?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Booleans are stored as integers, but `defaults` prints both kinds as 0/1
+fn parse_bool(value: Option<String>, default: bool) -> bool {
+    match value.as_deref() {
+        Some("1") | Some("true") | Some("YES") => true,
+        Some("0") | Some("false") | Some("NO") => false,
+        _ => default,
+    }
+}
+
+fn bool_args(value: bool) -> [&'static str; 2] {
+    ["-int", if value { "1" } else { "0" }]
+}
+
+impl AlertSettings {
+    pub fn load() -> Result<Self, String> {
+        Ok(Self {
+            sound: read_default(GLOBAL_DOMAIN, BEEP_SOUND)?
+                .unwrap_or_else(|| DEFAULT_ALERT_SOUND.to_string()),
+            ui_sounds: parse_bool(read_default(SYSTEM_SOUND_DOMAIN, UI_AUDIO)?, true),
+            volume_feedback: parse_bool(read_default(GLOBAL_DOMAIN, BEEP_FEEDBACK)?, false),
+        })
+    }
+
+    /// Writes whatever differs from `old`
+    pub fn save(&self, old: &AlertSettings) -> Result<(), String> {
+        if self.sound != old.sound {
+            write_default(GLOBAL_DOMAIN, BEEP_SOUND, &["-string", &self.sound])?;
+        }
+        if self.ui_sounds != old.ui_sounds {
+            write_default(SYSTEM_SOUND_DOMAIN, UI_AUDIO, &bool_args(self.ui_sounds))?;
+        }
+        if self.volume_feedback != old.volume_feedback {
+            write_default(
+                GLOBAL_DOMAIN,
+                BEEP_FEEDBACK,
+                &bool_args(self.volume_feedback),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn sounds_in(folder: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut sounds = Vec::new();
+    for entry in std::fs::read_dir(folder).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        let is_sound = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| SOUND_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+        if is_sound {
+            sounds.push(path);
+        }
+    }
+    Ok(sounds)
+}
+
+/// The built in alert sounds, followed by the user's own from ~/Library/Sounds
+pub fn list_alert_sounds() -> Result<Vec<PathBuf>, String> {
+    let mut sounds = sounds_in(Path::new(SYSTEM_SOUNDS))?;
+    sounds.sort();
+
+    if let Ok(homedir) = home_dir() {
+        let user_sounds = homedir.join("Library/Sounds");
+        if user_sounds.exists() {
+            let mut user = sounds_in(&user_sounds)?;
+            user.sort();
+            sounds.extend(user);
+        }
+    }
+
+    Ok(sounds)
+}
+
+/// The name System Settings shows for a sound
+pub fn sound_name(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// Plays a sound without waiting for it to finish
+pub fn preview(path: &str) -> Result<(), String> {
+    let mut child = std::process::Command::new("afplay")
+        .arg(path)
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    // Wait on another thread so it doesn't stay around as a zombie
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(())
+}
@@ -0,0 +1,463 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Audio devices. Listed with system_profiler, switched with CoreAudio (there is no command for it)
+
+use crate::command_output;
+
+const YES: &str = "spaudio_yes";
+const TRANSPORT_PREFIX: &str = "coreaudio_device_type_";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DeviceRole {
+    Output,
+    Input,
+    // Where alerts and UI sounds play
+    SystemOutput,
+}
+
+impl DeviceRole {
+    pub fn name(self) -> &'static str {
+        match self {
+            DeviceRole::Output => "output",
+            DeviceRole::Input => "input",
+            DeviceRole::SystemOutput => "alerts",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct AudioDevice {
+    pub name: String,
+    // CoreAudio's persistent UID, used to switch to the device. None when it couldn't be told
+    // apart from another device with the same name
+    pub uid: Option<String>,
+    pub manufacturer: Option<String>,
+    // Like "Built-in", "USB" or "Bluetooth"
+    pub transport: String,
+    // In Hz
+    pub sample_rate: Option<f64>,
+    pub input_channels: u32,
+    pub output_channels: u32,
+    pub default_output: bool,
+    pub default_input: bool,
+    pub default_system_output: bool,
+}
+
+impl AudioDevice {
+    pub fn is_default(&self, role: DeviceRole) -> bool {
+        match role {
+            DeviceRole::Output => self.default_output,
+            DeviceRole::Input => self.default_input,
+            DeviceRole::SystemOutput => self.default_system_output,
+        }
+    }
+
+    pub fn supports(&self, role: DeviceRole) -> bool {
+        match role {
+            DeviceRole::Output | DeviceRole::SystemOutput => self.output_channels > 0,
+            DeviceRole::Input => self.input_channels > 0,
+        }
+    }
+}
+
+/// Turns "coreaudio_device_type_builtin" into "Built-in"
+fn transport_name(transport: &str) -> String {
+    match transport
+        .strip_prefix(TRANSPORT_PREFIX)
+        .unwrap_or(transport)
+    {
+        "builtin" => String::from("Built-in"),
+        "usb" => String::from("USB"),
+        "bluetooth" => String::from("Bluetooth"),
+        "hdmi" => String::from("HDMI"),
+        "displayport" => String::from("DisplayPort"),
+        "airplay" => String::from("AirPlay"),
+        "thunderbolt" => String::from("Thunderbolt"),
+        "virtual" => String::from("Virtual"),
+        "aggregate" => String::from("Aggregate"),
+        "" => String::from("Unknown"),
+        other => other.to_string(),
+    }
+}
+
+/// Parses the output of `system_profiler -json SPAudioDataType`
+pub fn parse_audio_devices(output: &str) -> Result<Vec<AudioDevice>, String> {
+    let value = json::parse(output).map_err(|e| e.to_string())?;
+
+    let mut devices = Vec::new();
+    for group in value["SPAudioDataType"].members() {
+        for device in group["_items"].members() {
+            let Some(name) = device["_name"].as_str() else {
+                continue;
+            };
+
+            devices.push(AudioDevice {
+                name: name.to_string(),
+                uid: None,
+                manufacturer: device["coreaudio_device_manufacturer"]
+                    .as_str()
+                    .map(str::to_string),
+                transport: transport_name(
+                    device["coreaudio_device_transport"]
+                        .as_str()
+                        .unwrap_or_default(),
+                ),
+                sample_rate: device["coreaudio_device_srate"].as_f64(),
+                input_channels: device["coreaudio_device_input"]
+                    .as_u32()
+                    .unwrap_or_default(),
+                output_channels: device["coreaudio_device_output"]
+                    .as_u32()
+                    .unwrap_or_default(),
+                default_output: device["coreaudio_default_audio_output_device"] == YES,
+                default_input: device["coreaudio_default_audio_input_device"] == YES,
+                default_system_output: device["coreaudio_default_audio_system_device"] == YES,
+            });
+        }
+    }
+
+    Ok(devices)
+}
+
+/// Gives devices their CoreAudio UID from `uids` (name, UID). system_profiler only has names,
+/// so a device only gets one if its name is unique on both sides
+pub fn attach_uids(devices: &mut [AudioDevice], uids: &[(String, String)]) {
+    let found: Vec<Option<String>> = devices
+        .iter()
+        .map(|device| {
+            let listed = devices
+                .iter()
+                .filter(|other| other.name == device.name)
+                .count();
+            let mut matching = uids.iter().filter(|(name, _)| *name == device.name);
+            match (listed, matching.next(), matching.next()) {
+                (1, Some((_, uid)), None) => Some(uid.clone()),
+                _ => None,
+            }
+        })
+        .collect();
+
+    for (device, uid) in devices.iter_mut().zip(found) {
+        device.uid = uid;
+    }
+}
+
+/// Slow (system_profiler takes a second or two), so only call from a background thread
+pub fn get_audio_devices() -> Result<Vec<AudioDevice>, String> {
+    let mut devices = parse_audio_devices(&command_output!(
+        "system_profiler",
+        "-json",
+        "SPAudioDataType"
+    ))?;
+    attach_uids(&mut devices, &device_uids()?);
+    Ok(devices)
+}
+
+#[cfg(target_os = "macos")]
+fn property_address(selector: u32) -> objc2_core_audio::AudioObjectPropertyAddress {
+    objc2_core_audio::AudioObjectPropertyAddress {
+        mSelector: selector,
+        mScope: objc2_core_audio::kAudioObjectPropertyScopeGlobal,
+        mElement: objc2_core_audio::kAudioObjectPropertyElementMain,
+    }
+}
+
+/// The CoreAudio ids of every device
+#[cfg(target_os = "macos")]
+fn device_ids() -> Result<Vec<u32>, String> {
+    use objc2_core_audio::{
+        AudioObjectGetPropertyData, AudioObjectGetPropertyDataSize, kAudioHardwarePropertyDevices,
+        kAudioObjectSystemObject,
+    };
+    use std::ptr::NonNull;
+
+    let address = property_address(kAudioHardwarePropertyDevices);
+    let mut size: u32 = 0;
+    let status = unsafe {
+        AudioObjectGetPropertyDataSize(
+            kAudioObjectSystemObject as u32,
+            NonNull::from(&address),
+            0,
+            std::ptr::null(),
+            NonNull::from(&mut size),
+        )
+    };
+    if status != 0 {
+        return Err(format!("Could not count audio devices (OSStatus {status})"));
+    }
+
+    let mut ids = vec![0u32; size as usize / size_of::<u32>()];
+    let status = unsafe {
+        AudioObjectGetPropertyData(
+            kAudioObjectSystemObject as u32,
+            NonNull::from(&address),
+            0,
+            std::ptr::null(),
+            NonNull::from(&mut size),
+            NonNull::new(ids.as_mut_ptr().cast()).unwrap(),
+        )
+    };
+    if status != 0 {
+        return Err(format!("Could not list audio devices (OSStatus {status})"));
+    }
+
+    ids.truncate(size as usize / size_of::<u32>());
+    Ok(ids)
+}
+
+/// Reads a CFString property of a device, like its name or UID
+#[cfg(target_os = "macos")]
+fn string_property(id: u32, selector: u32, what: &str) -> Result<String, String> {
+    use objc2_core_audio::AudioObjectGetPropertyData;
+    use objc2_core_foundation::{CFRetained, CFString};
+    use std::ptr::NonNull;
+
+    let address = property_address(selector);
+    let mut name: *mut CFString = std::ptr::null_mut();
+    let mut size = size_of::<*mut CFString>() as u32;
+    let status = unsafe {
+        AudioObjectGetPropertyData(
+            id,
+            NonNull::from(&address),
+            0,
+            std::ptr::null(),
+            NonNull::from(&mut size),
+            NonNull::from(&mut name).cast(),
+        )
+    };
+    if status != 0 {
+        return Err(format!(
+            "Could not get the {what} of audio device {id} (OSStatus {status})"
+        ));
+    }
+
+    // The caller owns the returned string
+    match NonNull::new(name) {
+        Some(name) => Ok(unsafe { CFRetained::from_raw(name) }.to_string()),
+        None => Err(format!("Audio device {id} has no {what}")),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn device_uid(id: u32) -> Result<String, String> {
+    string_property(id, objc2_core_audio::kAudioDevicePropertyDeviceUID, "UID")
+}
+
+/// The name and UID of every device
+#[cfg(target_os = "macos")]
+fn device_uids() -> Result<Vec<(String, String)>, String> {
+    device_ids()?
+        .into_iter()
+        .map(|id| {
+            Ok((
+                string_property(id, objc2_core_audio::kAudioObjectPropertyName, "name")?,
+                device_uid(id)?,
+            ))
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "macos"))]
+fn device_uids() -> Result<Vec<(String, String)>, String> {
+    Ok(Vec::new())
+}
+
+/// Makes the device with this UID the default for a role
+#[cfg(target_os = "macos")]
+pub fn set_default_device(role: DeviceRole, uid: &str) -> Result<(), String> {
+    use log::info;
+    use objc2_core_audio::{
+        AudioObjectSetPropertyData, kAudioHardwarePropertyDefaultInputDevice,
+        kAudioHardwarePropertyDefaultOutputDevice, kAudioHardwarePropertyDefaultSystemOutputDevice,
+        kAudioObjectSystemObject,
+    };
+    use std::ptr::NonNull;
+
+    let mut found = None;
+    for id in device_ids()? {
+        if device_uid(id)? == uid {
+            found = Some(id);
+            break;
+        }
+    }
+    let Some(id) = found else {
+        return Err(format!("No audio device with the UID {uid}"));
+    };
+    info!("Setting the default {} device to {uid} ({id})", role.name());
+
+    let address = property_address(match role {
+        DeviceRole::Output => kAudioHardwarePropertyDefaultOutputDevice,
+        DeviceRole::Input => kAudioHardwarePropertyDefaultInputDevice,
+        DeviceRole::SystemOutput => kAudioHardwarePropertyDefaultSystemOutputDevice,
+    });
+    let status = unsafe {
+        AudioObjectSetPropertyData(
+            kAudioObjectSystemObject as u32,
+            NonNull::from(&address),
+            0,
+            std::ptr::null(),
+            size_of::<u32>() as u32,
+            NonNull::from(&id).cast(),
+        )
+    };
+    if status != 0 {
+        return Err(format!(
+            "Could not set the default {} device (OSStatus {status})",
+            role.name()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn set_default_device(_role: DeviceRole, _uid: &str) -> Result<(), String> {
+    Err("Switching audio devices is not supported on this system.".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Trimmed `system_profiler -json SPAudioDataType` output
+    const SP_AUDIO: &str = r#"{
+  "SPAudioDataType" : [
+    {
+      "_items" : [
+        {
+          "_name" : "MacBook Pro Microphone",
+          "coreaudio_default_audio_input_device" : "spaudio_yes",
+          "coreaudio_device_input" : 1,
+          "coreaudio_device_manufacturer" : "Apple Inc.",
+          "coreaudio_device_srate" : 48000,
+          "coreaudio_device_transport" : "coreaudio_device_type_builtin",
+          "coreaudio_input_source" : "MacBook Pro Microphone"
+        },
+        {
+          "_name" : "MacBook Pro Speakers",
+          "coreaudio_default_audio_output_device" : "spaudio_yes",
+          "coreaudio_default_audio_system_device" : "spaudio_yes",
+          "coreaudio_device_manufacturer" : "Apple Inc.",
+          "coreaudio_device_output" : 2,
+          "coreaudio_device_srate" : 48000,
+          "coreaudio_device_transport" : "coreaudio_device_type_builtin",
+          "coreaudio_output_source" : "MacBook Pro Speakers"
+        },
+        {
+          "_name" : "USB Audio Device",
+          "coreaudio_device_input" : 2,
+          "coreaudio_device_output" : 2,
+          "coreaudio_device_srate" : 44100,
+          "coreaudio_device_transport" : "coreaudio_device_type_usb"
+        },
+        {
+          "coreaudio_device_output" : 2
+        }
+      ],
+      "_name" : "coreaudio_device"
+    }
+  ]
+}"#;
+
+    #[test]
+    fn parses_devices() {
+        let devices = parse_audio_devices(SP_AUDIO).unwrap();
+        assert_eq!(devices.len(), 3);
+
+        let microphone = &devices[0];
+        assert_eq!(microphone.name, "MacBook Pro Microphone");
+        assert_eq!(microphone.manufacturer.as_deref(), Some("Apple Inc."));
+        assert_eq!(microphone.transport, "Built-in");
+        assert_eq!(microphone.sample_rate, Some(48000.0));
+        assert!(microphone.supports(DeviceRole::Input));
+        assert!(!microphone.supports(DeviceRole::Output));
+        assert!(microphone.is_default(DeviceRole::Input));
+        assert!(!microphone.is_default(DeviceRole::Output));
+
+        let speakers = &devices[1];
+        assert!(speakers.is_default(DeviceRole::Output));
+        assert!(speakers.is_default(DeviceRole::SystemOutput));
+        assert!(!speakers.supports(DeviceRole::Input));
+
+        let usb = &devices[2];
+        assert_eq!(usb.transport, "USB");
+        assert_eq!(usb.manufacturer, None);
+        assert!(usb.supports(DeviceRole::Input) && usb.supports(DeviceRole::Output));
+        assert!(!usb.is_default(DeviceRole::Output));
+        assert!(devices.iter().all(|device| device.uid.is_none()));
+    }
+
+    #[test]
+    fn rejects_malformed_output() {
+        assert!(parse_audio_devices("not json").is_err());
+        assert!(parse_audio_devices("{}").unwrap().is_empty());
+    }
+
+    fn uids(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, uid)| (name.to_string(), uid.to_string()))
+            .collect()
+    }
+
+    fn attached(devices: &[AudioDevice]) -> Vec<Option<&str>> {
+        devices.iter().map(|device| device.uid.as_deref()).collect()
+    }
+
+    #[test]
+    fn attaches_only_unique_uids() {
+        let mut devices = parse_audio_devices(SP_AUDIO).unwrap();
+        // Two identical USB devices
+        devices.push(devices[2].clone());
+        attach_uids(
+            &mut devices,
+            &uids(&[
+                ("MacBook Pro Microphone", "BuiltInMicrophoneDevice"),
+                ("MacBook Pro Speakers", "BuiltInSpeakerDevice"),
+                ("USB Audio Device", "AppleUSBAudioEngine:1"),
+                ("USB Audio Device", "AppleUSBAudioEngine:2"),
+            ]),
+        );
+        assert_eq!(
+            attached(&devices),
+            [
+                Some("BuiltInMicrophoneDevice"),
+                Some("BuiltInSpeakerDevice"),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn name_listed_twice_by_coreaudio_gets_no_uid() {
+        let mut devices = parse_audio_devices(SP_AUDIO).unwrap();
+        attach_uids(
+            &mut devices,
+            &uids(&[
+                ("MacBook Pro Microphone", "BuiltInMicrophoneDevice"),
+                ("MacBook Pro Microphone", "AggregateDevice"),
+                ("MacBook Pro Speakers", "BuiltInSpeakerDevice"),
+            ]),
+        );
+        assert_eq!(
+            attached(&devices),
+            [None, Some("BuiltInSpeakerDevice"), None]
+        );
+    }
+}
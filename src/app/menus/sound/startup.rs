@@ -0,0 +1,116 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// The startup chime. Apple Silicon and T2 Macs on Big Sur or later read the StartupMute NVRAM variable:
+//   unset  The chime plays (the default)
+//   %00    The chime plays
+//   %01    Muted
+
+use crate::app::helpers::{decode_nvram_value, run_as_admin};
+use crate::command_output;
+
+const STARTUP_MUTE: &str = "StartupMute";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StartupChime {
+    // The variable isn't set, so the chime plays
+    Default,
+    Enabled,
+    Muted,
+}
+
+impl StartupChime {
+    pub fn plays(self) -> bool {
+        self != StartupChime::Muted
+    }
+
+    pub fn explanation(self) -> &'static str {
+        match self {
+            StartupChime::Default => {
+                "StartupMute is not set, so the chime plays at startup (the default)."
+            }
+            StartupChime::Enabled => "StartupMute is %00, so the chime plays at startup.",
+            StartupChime::Muted => "StartupMute is %01, so the Mac starts up silently.",
+        }
+    }
+}
+
+/// Parses the output of `nvram StartupMute`. An unset variable prints nothing
+pub fn parse_startup_mute(output: &str) -> Result<StartupChime, String> {
+    let output = output.trim_end_matches('\n');
+    if output.is_empty() {
+        return Ok(StartupChime::Default);
+    }
+
+    let value = match output.split_once('\t') {
+        Some((STARTUP_MUTE, value)) => value,
+        _ => return Err(format!("Unexpected nvram output: {output:?}")),
+    };
+
+    match decode_nvram_value(value)?.as_slice() {
+        [0] => Ok(StartupChime::Enabled),
+        [1] => Ok(StartupChime::Muted),
+        other => Err(format!("Unexpected StartupMute value {other:?}")),
+    }
+}
+
+pub fn get_startup_chime() -> Result<StartupChime, String> {
+    parse_startup_mute(&command_output!("nvram", STARTUP_MUTE))
+}
+
+/// Writing NVRAM needs root, so this shows the authorization prompt
+pub fn set_startup_chime(plays: bool) -> Result<(), String> {
+    let value = if plays { "%00" } else { "%01" };
+    run_as_admin("nvram", &[&format!("{STARTUP_MUTE}={value}")])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unset_is_default() {
+        assert_eq!(parse_startup_mute(""), Ok(StartupChime::Default));
+        assert_eq!(parse_startup_mute("\n"), Ok(StartupChime::Default));
+    }
+
+    #[test]
+    fn parses_set_values() {
+        assert_eq!(
+            parse_startup_mute("StartupMute\t%00\n"),
+            Ok(StartupChime::Enabled)
+        );
+        assert_eq!(
+            parse_startup_mute("StartupMute\t%01\n"),
+            Ok(StartupChime::Muted)
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_output() {
+        // Not separated by a tab
+        assert!(parse_startup_mute("StartupMute %01").is_err());
+        // Another variable
+        assert!(parse_startup_mute("SystemAudioVolume\t%01").is_err());
+        // Unknown and truncated values
+        assert!(parse_startup_mute("StartupMute\t%02").is_err());
+        assert!(parse_startup_mute("StartupMute\t%0").is_err());
+        assert!(parse_startup_mute("StartupMute\t").is_err());
+    }
+}
@@ -0,0 +1,112 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Every volume read and write spawns osascript, so they happen on a background thread.
+// While the slider is dragged, values queue up during a write and only the newest is applied.
+// When idle, the worker polls so changes made elsewhere (keyboard, menu bar) show up.
+
+use super::{VolumeSettings, get_volume_settings, set_volume_settings};
+use log::debug;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, channel};
+use std::time::Duration;
+
+use eframe::egui;
+
+// How often to check for changes made outside of PrefSuite
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+pub enum WorkerEvent {
+    // Read from the system
+    Polled(Result<VolumeSettings, String>),
+    // The result of applying settings
+    Applied(Result<VolumeSettings, String>),
+}
+
+/// Stops when dropped
+pub struct VolumeWorker {
+    sender: Sender<VolumeSettings>,
+    receiver: Receiver<WorkerEvent>,
+}
+
+fn run(ctx: egui::Context, requests: Receiver<VolumeSettings>, events: Sender<WorkerEvent>) {
+    // What the system is set to, as far as we know
+    let mut current = None;
+    // Read right away the first time. After that wait between polls, even failed ones
+    let mut timeout = Duration::ZERO;
+
+    loop {
+        let event = match requests.recv_timeout(timeout) {
+            Ok(mut wanted) => {
+                // Skip to the newest value
+                let mut skipped = 0;
+                for newer in requests.try_iter() {
+                    wanted = newer;
+                    skipped += 1;
+                }
+                debug!("Applying volume settings ({skipped} older values skipped)");
+
+                let res = match &current {
+                    Some(old) => set_volume_settings(&wanted, old).map(|_| wanted),
+                    None => Err(String::from("The volume settings haven't been read yet")),
+                };
+                if res.is_ok() {
+                    current = Some(wanted);
+                }
+                WorkerEvent::Applied(res)
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                let res = get_volume_settings();
+                if let Ok(settings) = &res {
+                    current = Some(*settings);
+                }
+                WorkerEvent::Polled(res)
+            }
+            // The worker was dropped
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+
+        if events.send(event).is_err() {
+            return;
+        }
+        ctx.request_repaint();
+        timeout = POLL_INTERVAL;
+    }
+}
+
+impl VolumeWorker {
+    /// Starts the worker. It reads the settings right away
+    pub fn start(ctx: &egui::Context) -> Self {
+        let (sender, requests) = channel();
+        let (events, receiver) = channel();
+
+        let ctx = ctx.clone();
+        std::thread::spawn(move || run(ctx, requests, events));
+
+        Self { sender, receiver }
+    }
+
+    /// Queues settings to be applied
+    pub fn apply(&self, settings: VolumeSettings) {
+        // Can only fail if the thread died, which `events` will show
+        let _ = self.sender.send(settings);
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = WorkerEvent> + '_ {
+        self.receiver.try_iter()
+    }
+}
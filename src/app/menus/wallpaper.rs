@@ -16,13 +16,35 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+mod backup;
+mod desktop_plist;
+mod desktoppicture;
+mod dock;
+mod dynamic;
+mod gallery;
+mod generated;
+mod image_check;
+mod placement;
+pub mod slideshow;
+
+use crate::app::applescript::{self, Value};
 use crate::app::{Menu, MyApp};
-use rusqlite::Connection;
-use std::path::PathBuf;
+use desktoppicture::DesktopPictureDb;
+use dynamic::DynamicSchedule;
+use image_check::{ImageCheck, check_image};
+use placement::{Placement, PlacementSettings};
+use slideshow::{SlideshowConfig, SlideshowMode};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, channel};
 
 use eframe::egui;
 use eframe::egui::RichText;
 
+// What was found in a file's dynamic wallpaper metadata
+type DynamicResult = Result<Vec<DynamicSchedule>, String>;
+// What was found, and the local minute of day it was read at
+type DynamicRead = (DynamicResult, Option<u32>);
+
 #[derive(Default)]
 pub struct WallpaperData {
     new_path: Option<String>,
@@ -35,183 +57,690 @@ pub struct WallpaperData {
     // Whether wallpaper data is out of date
     reloadneeded: Option<bool>,
     wpaper: Option<Result<String, String>>,
+    // Every display/Space and its wallpaper
+    desktops: Option<Result<Vec<Desktop>, String>>,
+    // The result of restoring a desktoppicture.db backup
+    restore_result: Option<Result<String, String>>,
+    // Slideshow config being edited
+    slideshow: Option<Result<SlideshowConfig, String>>,
+    // The images the slideshow will rotate through
+    slideshow_preview: Option<Result<Vec<PathBuf>, String>>,
+    // Time input for a new schedule entry
+    slideshow_time: String,
+    // Result of the last slideshow action
+    slideshow_message: Option<String>,
+    gallery: gallery::GalleryData,
+    // Whether the picked file can be used as a wallpaper
+    new_path_check: Option<ImageCheck>,
+    // Solid color and gradient wallpapers
+    generator: generated::GeneratorData,
+    // Placement being edited
+    placement: Option<Result<PlacementSettings, String>>,
+    // The wallpaper the placement preview is for, and its size in pixels if it could be read
+    preview_size: Option<(PathBuf, Option<(u32, u32)>)>,
+    // Result of applying the placement
+    placement_result: Option<Result<(), String>>,
+    // The file that was inspected for dynamic wallpaper metadata, what was found,
+    // and the local minute of day it was read at, for the current frame
+    dynamic: Option<(String, DynamicResult, Option<u32>)>,
+    // A file being read for dynamic wallpaper metadata on a background thread
+    dynamic_loading: Option<(String, Receiver<DynamicRead>)>,
+    // A change running on a background thread
+    task: Option<Task>,
+}
+
+/// What a background task produced. Tasks are for slow work: anything that can restart the Dock
+/// (which takes seconds), or that decodes a whole image
+enum TaskResult {
+    // The wallpaper that was applied, for the gallery history
+    Change(Result<String, String>),
+    Placement(Result<(), String>),
+    Restore(Result<String, String>),
+    // A picked (or converted) file and whether it can be used as a wallpaper
+    Picked(Result<(String, ImageCheck), String>),
+    // Where a solid color or gradient was applied
+    Generated(Result<String, String>),
 }
 
-fn kill_dock() {
-    let s = sysinfo::System::new_all();
+struct Task {
+    // Shown next to the spinner
+    label: &'static str,
+    receiver: Receiver<TaskResult>,
+}
+
+/// Runs a task on a background thread. Buttons that start tasks are disabled while one runs,
+/// this only catches a second click in the same frame
+fn start_task(
+    data: &mut WallpaperData,
+    ctx: &egui::Context,
+    label: &'static str,
+    task: impl FnOnce() -> TaskResult + Send + 'static,
+) {
+    if data.task.is_some() {
+        return;
+    }
 
-    for process in s.processes().values() {
-        if process.name() == "Dock" {
-            process.kill();
+    let (sender, receiver) = channel();
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        // Nobody is waiting anymore if this fails
+        let _ = sender.send(task());
+        ctx.request_repaint();
+    });
+
+    data.task = Some(Task { label, receiver });
+}
+
+/// Hands the result of a finished task to whatever shows it
+fn receive_task(data: &mut WallpaperData) {
+    let Some(task) = &data.task else {
+        return;
+    };
+    let Ok(result) = task.receiver.try_recv() else {
+        return;
+    };
+    data.task = None;
+
+    match result {
+        TaskResult::Change(Ok(wallpaper)) => {
+            data.gallery.record_history(&wallpaper);
+            data.changerror = None;
+            data.reloadneeded = Some(true);
+        }
+        TaskResult::Change(Err(e)) => {
+            data.changerror = Some(e);
+        }
+        TaskResult::Placement(res) => {
+            data.placement_result = Some(res);
+        }
+        TaskResult::Restore(res) => {
+            if res.is_ok() {
+                data.reloadneeded = Some(true);
+            }
+            data.restore_result = Some(res);
+        }
+        TaskResult::Picked(Ok((path, check))) => {
+            data.new_path_check = Some(check);
+            data.new_path = Some(path);
+            data.changerror = None;
+        }
+        TaskResult::Picked(Err(e)) => {
+            data.changerror = Some(format!("Conversion failed: {e}"));
+        }
+        TaskResult::Generated(res) => {
+            if res.is_ok() {
+                data.reloadneeded = Some(true);
+            }
+            data.generator.applied(res);
         }
     }
 }
 
-// TODO: This
-fn get_current_wallpaper_pre_mavericks() -> Result<String, String> {
-    Ok("".into())
+#[derive(Clone)]
+enum DesktopId {
+    // A row of the pictures table in desktoppicture.db
+    Picture(i64),
+    // A System Events desktop (1-based)
+    SystemEvents(usize),
+    // A display in com.apple.desktop.plist
+    Plist(String),
 }
 
-fn get_current_wallpaper_mavericks_to_sonoma() -> Result<String, String> {
-    let homedir = std::env::var("HOME");
-    if homedir.is_err() {
-        return Err(String::from("HOME not set"));
-    }
-    let homedir = homedir.unwrap();
-
-    let db: PathBuf = [
-        &homedir,
-        "Library/Application Support/Dock/desktoppicture.db",
-    ]
-    .iter()
-    .collect();
-    if !db.exists() {
-        return Err(String::from("Database file not found :("));
-    }
+struct Desktop {
+    id: DesktopId,
+    name: String,
+    wallpaper: Option<String>,
+    // The CGDirectDisplayID of the display it's on. None for desktops that apply to every display,
+    // or when the backend doesn't know it
+    display_id: Option<u32>,
+}
 
-    let conn = Connection::open(db);
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "heic", "heif", "tiff", "tif", "gif", "bmp", "webp", "avif", "tga",
+];
+
+/// The images in a folder, sorted by name
+pub fn images_in(folder: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut images: Vec<PathBuf> = std::fs::read_dir(folder)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        })
+        .collect();
+
+    images.sort();
+    Ok(images)
+}
 
-    if let Err(con) = conn {
-        return Err(con.to_string());
+/// A file in `cache` named after an image. Changes when the image does
+pub fn cached_file_path(cache: &Path, image: &Path) -> Result<PathBuf, String> {
+    use std::hash::{Hash, Hasher};
+
+    let metadata = std::fs::metadata(image).map_err(|e| e.to_string())?;
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    image.hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    metadata.modified().ok().hash(&mut hasher);
+
+    Ok(cache.join(format!("{:016x}.png", hasher.finish())))
+}
+
+/// Where wallpapers are stored, which depends on the macOS version
+#[derive(Clone, Copy, PartialEq)]
+enum Backend {
+    // com.apple.desktop.plist
+    PreMavericks,
+    // desktoppicture.db
+    MavericksToSonoma,
+    // System Events and NSWorkspace
+    SonomaPlus,
+}
+
+impl Backend {
+    fn current() -> Self {
+        let binding = os_info::get();
+        let version = binding.version();
+
+        if version >= &os_info::Version::Semantic(14, 0, 0) {
+            Backend::SonomaPlus
+        } else if version >= &os_info::Version::Semantic(10, 9, 0) {
+            Backend::MavericksToSonoma
+        } else {
+            Backend::PreMavericks
+        }
     }
 
-    let conn = conn.unwrap();
+    /// Whether the Dock only sees changes after a restart.
+    /// System Events and NSWorkspace tell the Dock themselves
+    fn needs_dock_restart(self) -> bool {
+        self != Backend::SonomaPlus
+    }
+}
 
-    let mut stmt = conn
-        .prepare("SELECT cast(value as text) from data ORDER BY rowid DESC")
-        .unwrap();
+fn get_current_wallpaper_pre_mavericks() -> Result<String, String> {
+    let plist = desktop_plist::load(&desktop_plist::default_path()?)?;
 
-    let iter = stmt.query_map([], |row| row.get(0)).unwrap();
+    Ok(desktop_plist::current_wallpaper(&plist).unwrap_or_else(|| "Default Wallpaper".to_string()))
+}
 
-    let mut values: Vec<String> = Vec::new();
+fn get_current_wallpaper_mavericks_to_sonoma() -> Result<String, String> {
+    let db = DesktopPictureDb::open_default()?;
 
-    for value in iter {
-        values.push(value.unwrap());
+    Ok(db
+        .current_wallpaper()?
+        .unwrap_or_else(|| "Default Wallpaper".to_string()))
+}
+
+fn get_current_wallpaper_sonoma_plus() -> Result<String, String> {
+    let path = applescript::run(
+        "tell app \"finder\" to get posix path of (get desktop picture as alias)",
+    )?;
+
+    match path.as_str() {
+        Some(path) => Ok(path.to_string()),
+        None => Err(format!("Unexpected desktop picture: {path}")),
     }
+}
+
+fn change_wallpaper_pre_mavericks(new_path: &str) -> Result<(), String> {
+    let path = desktop_plist::default_path()?;
+
+    let mut plist = desktop_plist::load(&path)?;
+    desktop_plist::set_wallpaper_everywhere(&mut plist, new_path)?;
+    desktop_plist::save(&path, &plist)
+}
 
-    if values.len() == 3 {
-        Ok(values[1].clone())
-    } else if values.len() == 2 {
-        Ok(values[0].clone())
-    } else if values.is_empty() || values[0] == "3" {
-        Ok("Default Wallpaper".to_string())
+fn change_wallpaper_mavericks_to_sonoma(new_path: &str, backup: bool) -> Result<(), String> {
+    let db = if backup {
+        DesktopPictureDb::open_default()?
     } else {
-        Ok(values[0].clone())
+        DesktopPictureDb::open(&desktoppicture::default_path()?)?
+    };
+    db.write(|db| db.set_wallpaper_everywhere(new_path))?;
+    db.close()
+}
+
+fn change_wallpaper_sonoma_plus(new_path: &str) -> Result<(), String> {
+    applescript::run(&format!(
+        "tell application \"System Events\" to tell every desktop to set picture to {} as POSIX file",
+        Value::String(new_path.to_string())
+    ))?;
+    Ok(())
+}
+
+/// Makes sure an image can be used as a wallpaper, converting it to a PNG if needed
+fn prepare_wallpaper(path: &str) -> Result<String, String> {
+    match check_image(Path::new(path)) {
+        ImageCheck::Ok => Ok(path.to_string()),
+        ImageCheck::NeedsConversion(_) => Ok(image_check::cached_conversion(Path::new(path))?
+            .display()
+            .to_string()),
+        ImageCheck::Invalid(e) => Err(e),
     }
 }
 
-fn get_current_wallpaper_sonoma_plus() -> Result<String, String> {
-    let osascript = std::process::Command::new("osascript")
-        .arg("-e")
-        .arg("tell app \"finder\" to get posix path of (get desktop picture as alias)")
-        .output();
+/// The picked file, if it can be used as a wallpaper.
+/// Uses the check done when it was picked, so the image isn't decoded again on the UI thread
+fn picked_wallpaper(data: &WallpaperData) -> Result<String, String> {
+    let Some(path) = &data.new_path else {
+        return Err(String::from("You have to select an image."));
+    };
 
-    if let Err(osaerr) = osascript {
-        return Err(osaerr.to_string());
+    // Don't touch the Dock database for something it can't show
+    match &data.new_path_check {
+        Some(ImageCheck::Ok) => Ok(path.clone()),
+        Some(ImageCheck::NeedsConversion(format)) => Err(format!(
+            "{format} images can't be used as a wallpaper. Convert it first."
+        )),
+        Some(ImageCheck::Invalid(e)) => Err(format!("Invalid image: {e}")),
+        // Files are only picked together with their check
+        None => Err(String::from("The picked file hasn't been checked yet.")),
     }
-    let t1 = String::from_utf8(osascript.unwrap().stdout).unwrap();
+}
+
+/// `new_path` has to have been checked, by `prepare_wallpaper` or `picked_wallpaper`
+fn change_wallpaper(new_path: &str) -> Result<(), String> {
+    let backend = Backend::current();
+    dock::restart_after(
+        backend.needs_dock_restart(),
+        write_wallpaper(backend, new_path, true),
+    )
+}
 
-    match t1.strip_suffix('\n') {
-        Some(s) => Ok(s.to_string()),
-        None => Ok(t1),
+/// Sets the wallpaper of every desktop, without restarting the Dock.
+/// `backup` is whether desktoppicture.db is backed up first
+fn write_wallpaper(backend: Backend, new_path: &str, backup: bool) -> Result<(), String> {
+    match backend {
+        Backend::SonomaPlus => change_wallpaper_sonoma_plus(new_path),
+        Backend::MavericksToSonoma => change_wallpaper_mavericks_to_sonoma(new_path, backup),
+        Backend::PreMavericks => change_wallpaper_pre_mavericks(new_path),
     }
 }
 
-// TODO: Modify the plist file
-#[allow(unused_variables)]
-fn change_wallpaper_pre_mavericks(new_path: &str) -> Result<(), String> {
-    Ok(())
+/// For the slideshow daemon. Every switch taking a backup would push the user's own backups out,
+/// and the history would fill up with slideshow images, so neither happens here.
+/// `new_path` has to have gone through `prepare_wallpaper`
+fn change_wallpaper_unattended(new_path: &str) -> Result<(), String> {
+    let backend = Backend::current();
+    dock::restart_after(
+        backend.needs_dock_restart(),
+        write_wallpaper(backend, new_path, false),
+    )
 }
 
-/// This is only possible thanks to the amazing reverse engineering work done over here. Give them a star.
-/// https://github.com/tech-otaku/macos-desktop
-fn change_wallpaper_mavericks_to_sonoma(new_path: &str) -> Result<(), String> {
-    let homedir = std::env::var("HOME");
-    if homedir.is_err() {
-        return Err(String::from("HOME not set"));
+fn get_placement() -> Result<PlacementSettings, String> {
+    match Backend::current() {
+        Backend::SonomaPlus => placement::get_placement_sonoma_plus(),
+        Backend::MavericksToSonoma => DesktopPictureDb::open_default()?.current_placement(),
+        Backend::PreMavericks => {
+            let plist = desktop_plist::load(&desktop_plist::default_path()?)?;
+            Ok(desktop_plist::current_placement(&plist))
+        }
     }
-    let homedir = homedir.unwrap();
-
-    let db: PathBuf = [
-        &homedir,
-        "Library/Application Support/Dock/desktoppicture.db",
-    ]
-    .iter()
-    .collect();
-    if !db.exists() {
-        return Err(String::from("Database file not found :("));
+}
+
+fn set_placement(settings: &PlacementSettings) -> Result<(), String> {
+    let backend = Backend::current();
+    let res = match backend {
+        Backend::SonomaPlus => placement::set_placement_sonoma_plus(settings),
+        Backend::MavericksToSonoma => DesktopPictureDb::open_default().and_then(|db| {
+            db.write(|db| db.set_placement_everywhere(settings))?;
+            db.close()
+        }),
+        Backend::PreMavericks => desktop_plist::default_path().and_then(|path| {
+            let mut plist = desktop_plist::load(&path)?;
+            desktop_plist::set_placement_everywhere(&mut plist, settings)?;
+            desktop_plist::save(&path, &plist)
+        }),
+    };
+    dock::restart_after(backend.needs_dock_restart(), res)
+}
+
+fn show_placement(ui: &mut egui::Ui, data: &mut WallpaperData, wallpaper: &Path) {
+    if data.placement.is_none() {
+        data.placement = Some(get_placement());
     }
+    let settings = match data.placement.as_mut().unwrap() {
+        Ok(settings) => settings,
+        Err(e) => {
+            ui.label(format!("Failed to get the wallpaper placement: {e}"));
+            return;
+        }
+    };
+
+    let sonoma_plus = Backend::current() == Backend::SonomaPlus;
+    ui.horizontal_wrapped(|ui| {
+        for placement in Placement::ALL {
+            ui.add_enabled_ui(!sonoma_plus || placement.supported_on_sonoma_plus(), |ui| {
+                ui.radio_value(&mut settings.placement, placement, placement.name());
+            });
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Fill color:");
+        ui.color_edit_button_srgb(&mut settings.fill_color);
+    });
 
-    let conn = Connection::open(db);
+    // In pixels, so unscaled placements are previewed at the right size
+    let screen_width = ui.ctx().input(|i| {
+        i.viewport()
+            .monitor_size
+            .map(|size| size.x * i.viewport().native_pixels_per_point.unwrap_or(1.0))
+            .unwrap_or(1920.0)
+    });
+    // Reading the size opens the file, so only do it when the wallpaper changes
+    if data
+        .preview_size
+        .as_ref()
+        .is_none_or(|(path, _)| path != wallpaper)
+    {
+        data.preview_size = Some((
+            wallpaper.to_path_buf(),
+            image::image_dimensions(wallpaper).ok(),
+        ));
+    }
+    let image_size = data.preview_size.as_ref().and_then(|(_, size)| *size);
+    placement::show_preview(ui, wallpaper, image_size, settings, screen_width);
 
-    if let Err(con) = conn {
-        return Err(con.to_string());
+    let settings = *settings;
+    if ui
+        .add_enabled(data.task.is_none(), egui::Button::new("Apply placement"))
+        .clicked()
+    {
+        data.placement_result = None;
+        start_task(data, ui.ctx(), "Applying...", move || {
+            TaskResult::Placement(set_placement(&settings))
+        });
+    }
+    match &data.placement_result {
+        Some(Ok(_)) => {
+            ui.label("Placement applied.");
+        }
+        Some(Err(e)) => {
+            ui.label(format!("Failed to apply placement: {e}"));
+        }
+        None => {}
     }
+}
 
-    let conn = conn.unwrap();
+fn show_appearance(ui: &mut egui::Ui, appearance: &dynamic::Appearance) {
+    ui.label(format!(
+        "Light mode: frame {}, dark mode: frame {}",
+        appearance.light, appearance.dark
+    ));
+}
 
-    // Delete old data
-    conn.execute("DELETE FROM data;", ()).unwrap();
-    conn.execute("DELETE FROM preferences;", ()).unwrap();
+/// Reads a file's dynamic wallpaper metadata on a background thread
+/// Reads the metadata, and the time once for the whole load since it means running `date`
+fn start_read_dynamic(path: &str, ctx: &egui::Context) -> Receiver<DynamicRead> {
+    let (sender, receiver) = channel();
+    let path = PathBuf::from(path);
+    let ctx = ctx.clone();
 
-    // Add new data
-    conn.execute("INSERT INTO data(rowid,value) VALUES (1,?1)", [new_path])
-        .unwrap();
+    std::thread::spawn(move || {
+        // Nobody is waiting anymore if this fails
+        let _ = sender.send((
+            dynamic::read_dynamic(&path),
+            slideshow::local_minute_of_day().ok(),
+        ));
+        ctx.request_repaint();
+    });
 
-    conn.execute(
-        "INSERT INTO preferences(rowid,key,data_id,picture_id) VALUES (1,1,1,3)",
-        (),
-    )
-    .unwrap();
-    conn.execute(
-        "INSERT INTO preferences(rowid,key,data_id,picture_id) VALUES (2,1,1,4)",
-        (),
-    )
-    .unwrap();
-    conn.execute(
-        "INSERT INTO preferences(rowid,key,data_id,picture_id) VALUES (3,1,1,2)",
-        (),
-    )
-    .unwrap();
-    conn.execute(
-        "INSERT INTO preferences(rowid,key,data_id,picture_id) VALUES (4,1,1,1)",
-        (),
-    )
-    .unwrap();
+    receiver
+}
 
-    conn.close().unwrap();
+fn show_dynamic(ui: &mut egui::Ui, data: &mut WallpaperData, path: &str) {
+    if let Some((loading, receiver)) = &data.dynamic_loading
+        && let Ok((res, now)) = receiver.try_recv()
+    {
+        data.dynamic = Some((loading.clone(), res, now));
+        data.dynamic_loading = None;
+    }
 
-    Ok(())
+    let inspected = data
+        .dynamic
+        .as_ref()
+        .is_some_and(|(inspected, _, _)| inspected == path);
+    let loading = data
+        .dynamic_loading
+        .as_ref()
+        .is_some_and(|(loading, _)| loading == path);
+    if !inspected && !loading {
+        data.dynamic_loading = Some((path.to_string(), start_read_dynamic(path, ui.ctx())));
+    }
+
+    ui.horizontal(|ui| {
+        ui.label("Inspecting:");
+        ui.monospace(path);
+    });
+
+    if !inspected {
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label("Reading metadata...");
+        });
+        return;
+    }
+
+    let (_, schedules, now) = data.dynamic.as_ref().unwrap();
+    let schedules = match schedules {
+        Ok(schedules) if schedules.is_empty() => {
+            ui.label("This is not a dynamic wallpaper.");
+            return;
+        }
+        Ok(schedules) => schedules,
+        Err(e) => {
+            ui.label(format!("Failed to read dynamic wallpaper metadata: {e}"));
+            return;
+        }
+    };
+
+    for schedule in schedules {
+        match schedule {
+            DynamicSchedule::Solar {
+                appearance,
+                entries,
+            } => {
+                ui.label(RichText::new("Follows the sun:").heading());
+                if let Some(appearance) = appearance {
+                    show_appearance(ui, appearance);
+                }
+                egui::Grid::new("dynamic_solar")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label(RichText::new("Frame").strong());
+                        ui.label(RichText::new("Sun altitude").strong());
+                        ui.label(RichText::new("Sun azimuth").strong());
+                        ui.end_row();
+                        for entry in entries {
+                            ui.label(entry.frame.to_string());
+                            ui.label(format!("{:.1}°", entry.altitude));
+                            ui.label(format!("{:.1}°", entry.azimuth));
+                            ui.end_row();
+                        }
+                    });
+            }
+            DynamicSchedule::Time {
+                appearance,
+                entries,
+            } => {
+                ui.label(RichText::new("Follows the time of day:").heading());
+                if let Some(appearance) = appearance {
+                    show_appearance(ui, appearance);
+                }
+                if let Some(now) = now
+                    && let Some(frame) = dynamic::frame_at(entries, *now)
+                {
+                    ui.label(format!("Showing frame {frame} now."));
+                }
+                egui::Grid::new("dynamic_h24").striped(true).show(ui, |ui| {
+                    ui.label(RichText::new("From").strong());
+                    ui.label(RichText::new("Frame").strong());
+                    ui.end_row();
+                    for entry in entries {
+                        ui.label(slideshow::format_time_of_day(entry.minute_of_day));
+                        ui.label(entry.frame.to_string());
+                        ui.end_row();
+                    }
+                });
+            }
+            DynamicSchedule::Appearance(appearance) => {
+                ui.label(RichText::new("Follows light/dark mode:").heading());
+                show_appearance(ui, appearance);
+            }
+        }
+    }
 }
 
-fn change_wallpaper_sonoma_plus(new_path: &str) -> Result<(), String> {
-    match std::process::Command::new("osascript")
-        .arg("-e")
-        .arg(format!("tell application \"System Events\" to tell every desktop to set picture to \"{new_path}\" as POSIX file"))
-        .spawn()
-        .unwrap()
-        .wait() {
-        Ok(_) => Ok(()),
-        Err(e) => Err(e.to_string())
+fn get_desktops_mavericks_to_sonoma() -> Result<Vec<Desktop>, String> {
+    let db = DesktopPictureDb::open_default()?;
+    let displays = db.displays()?;
+    let spaces = db.spaces()?;
+
+    let mut desktops = Vec::new();
+    for picture in db.pictures()? {
+        desktops.push(Desktop {
+            id: DesktopId::Picture(picture.id),
+            name: DesktopPictureDb::describe(&picture, &displays, &spaces),
+            wallpaper: db.wallpaper(picture.id)?,
+            // The database only has display UUIDs, which NSScreen doesn't give out
+            display_id: None,
+        });
     }
+    Ok(desktops)
 }
 
-fn change_wallpaper(new_path: &str) -> Result<(), String> {
+/// System Events only knows about the desktops of the current Space
+fn get_desktops_sonoma_plus() -> Result<Vec<Desktop>, String> {
+    // {{ids...}, {display names...}, {pictures...}}. A desktop's id is its display's id
+    let desktops = applescript::run(
+        "tell application \"System Events\" to get {id, display name, picture} of every desktop",
+    )?;
+
+    let column = |index: usize| {
+        desktops
+            .as_list()
+            .and_then(|d| d.get(index))
+            .and_then(Value::as_list)
+    };
+    let (Some(ids), Some(names), Some(pictures)) = (column(0), column(1), column(2)) else {
+        return Err(format!("Unexpected desktops: {desktops}"));
+    };
+
+    Ok(ids
+        .iter()
+        .zip(names)
+        .zip(pictures)
+        .enumerate()
+        .map(|(index, ((id, name), picture))| Desktop {
+            id: DesktopId::SystemEvents(index + 1),
+            name: name.as_str().unwrap_or_default().to_string(),
+            wallpaper: picture.as_str().map(str::to_string),
+            display_id: id.as_integer().and_then(|id| u32::try_from(id).ok()),
+        })
+        .collect())
+}
+
+fn get_desktops_pre_mavericks() -> Result<Vec<Desktop>, String> {
+    let plist = desktop_plist::load(&desktop_plist::default_path()?)?;
+    let wallpapers = desktop_plist::wallpapers(&plist);
+
+    Ok(wallpapers
+        .into_iter()
+        .map(|(name, wallpaper)| Desktop {
+            id: DesktopId::Plist(name.clone()),
+            // Names end in the display id, except "default"
+            display_id: name.rsplit('/').next().and_then(|id| id.parse().ok()),
+            name,
+            wallpaper: Some(wallpaper),
+        })
+        .collect())
+}
+
+fn get_desktops() -> Result<Vec<Desktop>, String> {
     let binding = os_info::get();
     let version = binding.version();
 
     let sonoma = os_info::Version::Semantic(14, 0, 0);
     let mavericks = os_info::Version::Semantic(10, 9, 0);
 
-    let res = if version >= &sonoma {
-        change_wallpaper_sonoma_plus(new_path)
+    if version >= &sonoma {
+        get_desktops_sonoma_plus()
     } else if version >= &mavericks {
-        change_wallpaper_mavericks_to_sonoma(new_path)
+        get_desktops_mavericks_to_sonoma()
     } else {
-        change_wallpaper_pre_mavericks(new_path)
-    };
-    kill_dock();
-    res
+        get_desktops_pre_mavericks()
+    }
+}
+
+/// `new_path` has to have been checked, like for `change_wallpaper`
+fn change_desktop_wallpaper(desktop: DesktopId, new_path: &str) -> Result<(), String> {
+    change_desktop_wallpapers(&[(desktop, new_path.to_string())])
+}
+
+/// Sets the wallpapers of several desktops, with a single backup and Dock restart.
+/// The paths have to have been checked, like for `change_wallpaper`
+fn change_desktop_wallpapers(changes: &[(DesktopId, String)]) -> Result<(), String> {
+    let mut pictures = Vec::new();
+    let mut plist_displays = Vec::new();
+
+    for (desktop, new_path) in changes {
+        match desktop {
+            DesktopId::Picture(picture_id) => pictures.push((*picture_id, new_path)),
+            DesktopId::SystemEvents(index) => {
+                applescript::run(&format!(
+                    "tell application \"System Events\" to tell desktop {} to set picture to {} as POSIX file",
+                    Value::Integer(*index as i64),
+                    Value::String(new_path.to_string())
+                ))?;
+            }
+            DesktopId::Plist(name) => plist_displays.push((name, new_path)),
+        }
+    }
+
+    if !pictures.is_empty() {
+        let res = DesktopPictureDb::open_default().and_then(|db| {
+            db.write(|db| {
+                for (picture_id, new_path) in &pictures {
+                    db.set_wallpaper(*picture_id, new_path)?;
+                }
+                Ok(())
+            })?;
+            db.close()
+        });
+        return dock::restart_after(true, res);
+    }
+
+    if !plist_displays.is_empty() {
+        let path = desktop_plist::default_path()?;
+        let mut plist = desktop_plist::load(&path)?;
+        for (name, new_path) in &plist_displays {
+            desktop_plist::set_wallpaper(&mut plist, name, new_path)?;
+        }
+        return dock::restart_after(true, desktop_plist::save(&path, &plist));
+    }
+
+    Ok(())
+}
+
+/// Rolls desktoppicture.db back to before the last change
+fn restore_wallpaper_database() -> Result<String, String> {
+    let taken = dock::restart_after(
+        true,
+        backup::restore_latest(&desktoppicture::default_path()?, &backup::default_dir()?),
+    )?;
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_millis();
+    let minutes = now.saturating_sub(taken) / 60_000;
+    Ok(format!("Restored the backup from {minutes} minute(s) ago"))
 }
 
 fn get_current_wallpaper() -> Result<String, String> {
@@ -230,13 +759,143 @@ fn get_current_wallpaper() -> Result<String, String> {
     }
 }
 
+fn show_slideshow(ui: &mut egui::Ui, data: &mut WallpaperData) {
+    if data.slideshow.is_none() {
+        data.slideshow = Some(SlideshowConfig::load());
+    }
+    let config = match data.slideshow.as_mut().unwrap() {
+        Ok(config) => config,
+        Err(e) => {
+            ui.label(format!("Failed to load slideshow settings: {e}"));
+            return;
+        }
+    };
+
+    ui.horizontal(|ui| {
+        ui.radio_value(&mut config.mode, SlideshowMode::Off, "Off");
+        ui.radio_value(&mut config.mode, SlideshowMode::Interval, "Rotate a folder");
+        ui.radio_value(&mut config.mode, SlideshowMode::Schedule, "Times of day");
+    });
+
+    match config.mode {
+        SlideshowMode::Off => {}
+        SlideshowMode::Interval => {
+            ui.horizontal(|ui| {
+                if ui.button("Pick folder").clicked()
+                    && let Some(folder) = rfd::FileDialog::new().pick_folder()
+                {
+                    config.folder = folder.display().to_string();
+                    data.slideshow_preview = None;
+                }
+                ui.monospace(&config.folder);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Change every");
+                ui.add(egui::DragValue::new(&mut config.interval_minutes).range(1..=10080));
+                ui.label("minutes");
+            });
+            ui.checkbox(&mut config.shuffle, "Shuffle");
+
+            if !config.folder.is_empty() {
+                if data.slideshow_preview.is_none() {
+                    data.slideshow_preview = Some(images_in(Path::new(&config.folder)));
+                }
+                ui.collapsing("Images", |ui| {
+                    match data.slideshow_preview.as_ref().unwrap() {
+                        Ok(images) if images.is_empty() => {
+                            ui.label("There are no images in this folder.");
+                        }
+                        Ok(images) => {
+                            for image in images {
+                                ui.monospace(image.display().to_string());
+                            }
+                        }
+                        Err(e) => {
+                            ui.label(format!("Failed to read folder: {e}"));
+                        }
+                    }
+                });
+            }
+        }
+        SlideshowMode::Schedule => {
+            let mut remove = None;
+            for (index, entry) in config.schedule.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.monospace(slideshow::format_time_of_day(entry.minute_of_day));
+                    ui.monospace(&entry.path);
+                    if ui.button("Remove").clicked() {
+                        remove = Some(index);
+                    }
+                });
+            }
+            if let Some(index) = remove {
+                config.schedule.remove(index);
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("At (HH:MM):");
+                ui.add(egui::TextEdit::singleline(&mut data.slideshow_time).desired_width(60.0));
+                if let Some(new_path) = &data.new_path
+                    && ui.button("Use picked file").clicked()
+                {
+                    match slideshow::parse_time_of_day(&data.slideshow_time) {
+                        Some(minute_of_day) => {
+                            config.schedule.push(slideshow::ScheduleEntry {
+                                minute_of_day,
+                                path: new_path.clone(),
+                            });
+                            config.schedule.sort_by_key(|entry| entry.minute_of_day);
+                            data.slideshow_message = None;
+                        }
+                        None => {
+                            data.slideshow_message =
+                                Some(format!("Invalid time: {}", data.slideshow_time));
+                        }
+                    }
+                }
+            });
+        }
+    }
+
+    ui.horizontal(|ui| {
+        if ui.button("Save").clicked() {
+            data.slideshow_message = Some(match config.save() {
+                Ok(_) => "Saved.".to_string(),
+                Err(e) => format!("Failed to save: {e}"),
+            });
+        }
+
+        if slideshow::is_launch_agent_installed() {
+            if ui.button("Stop running at login").clicked() {
+                data.slideshow_message = Some(match slideshow::uninstall_launch_agent() {
+                    Ok(_) => "Removed the LaunchAgent.".to_string(),
+                    Err(e) => format!("Failed to remove the LaunchAgent: {e}"),
+                });
+            }
+        } else if ui.button("Run at login").clicked() {
+            data.slideshow_message = Some(match slideshow::install_launch_agent() {
+                Ok(_) => "Installed the LaunchAgent.".to_string(),
+                Err(e) => format!("Failed to install the LaunchAgent: {e}"),
+            });
+        }
+    });
+
+    if let Some(message) = &data.slideshow_message {
+        ui.label(message);
+    }
+}
+
 pub fn main(app: &mut MyApp, ctx: &egui::Context) {
+    receive_task(&mut app.wallpaper_data);
+
     if app.wallpaper_data.reloadneeded.is_none() {
         app.wallpaper_data.reloadneeded = Some(true);
     }
 
     let current_wallpaper = if app.wallpaper_data.reloadneeded.unwrap() {
         app.wallpaper_data.reloadneeded = Some(false);
+        app.wallpaper_data.desktops = None;
+        app.wallpaper_data.gallery.reload_history();
         let w = get_current_wallpaper();
         app.wallpaper_data.wpaper = Some(w.clone());
         w
@@ -252,6 +911,13 @@ pub fn main(app: &mut MyApp, ctx: &egui::Context) {
             ui.label(RichText::new("Wallpaper Menu:").size(36.0));
         });
 
+        if let Some(task) = &app.wallpaper_data.task {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(task.label);
+            });
+        }
+
         let wallpaper_path;
 
         match current_wallpaper {
@@ -267,9 +933,12 @@ pub fn main(app: &mut MyApp, ctx: &egui::Context) {
             }
         };
 
+        // Buttons that start a task are disabled until the running one finishes
+        let busy = app.wallpaper_data.task.is_some();
+
         egui::ScrollArea::vertical().show(ui, |ui| {
             if !wallpaper_path.is_empty() && !app.wallpaper_data.dberror {
-                let wallpaper_path = PathBuf::from(wallpaper_path);
+                let wallpaper_path = PathBuf::from(&wallpaper_path);
                 if wallpaper_path.exists() {
                     ui.collapsing("Wallpaper:", |ui| {
                         ui.image(String::from("file://") + wallpaper_path.to_str().unwrap());
@@ -280,31 +949,84 @@ pub fn main(app: &mut MyApp, ctx: &egui::Context) {
             }
 
             if !app.wallpaper_data.dberror {
-                if ui.button("Change Wallpaper").clicked()
+                if ui
+                    .add_enabled(!busy, egui::Button::new("Change Wallpaper"))
+                    .clicked()
                     && let Some(path) = rfd::FileDialog::new()
-                        //.add_filter("image", &["png", "jpg", "jpeg", "webp", "heic", "heif"])
+                        .add_filter("image", IMAGE_EXTENSIONS)
                         .pick_file()
                 {
-                    app.wallpaper_data.new_path = Some(path.display().to_string());
+                    // Decoding the whole image can take a while
+                    start_task(
+                        &mut app.wallpaper_data,
+                        ctx,
+                        "Checking image...",
+                        move || {
+                            let check = check_image(&path);
+                            TaskResult::Picked(Ok((path.display().to_string(), check)))
+                        },
+                    );
                 }
 
-                if let Some(picked_path) = &app.wallpaper_data.new_path {
+                if let Some(picked_path) = app.wallpaper_data.new_path.clone() {
                     ui.horizontal(|ui| {
                         ui.label("Picked file:");
-                        ui.monospace(picked_path);
+                        ui.monospace(&picked_path);
                     });
+
+                    match app.wallpaper_data.new_path_check.clone() {
+                        Some(ImageCheck::NeedsConversion(format)) => {
+                            ui.horizontal(|ui| {
+                                ui.label(format!("{format} images can't be used as a wallpaper."));
+                                if ui
+                                    .add_enabled(!busy, egui::Button::new("Convert to PNG"))
+                                    .clicked()
+                                {
+                                    let picked_path = picked_path.clone();
+                                    start_task(
+                                        &mut app.wallpaper_data,
+                                        ctx,
+                                        "Converting...",
+                                        move || {
+                                            TaskResult::Picked(
+                                                image_check::cached_conversion(Path::new(
+                                                    &picked_path,
+                                                ))
+                                                .map(|converted| {
+                                                    let check = check_image(&converted);
+                                                    (converted.display().to_string(), check)
+                                                }),
+                                            )
+                                        },
+                                    );
+                                }
+                            });
+                        }
+                        Some(ImageCheck::Invalid(e)) => {
+                            ui.label(format!("Not a usable image: {e}"));
+                        }
+                        _ => {}
+                    }
                 }
 
-                if ui.button("Change").clicked() {
-                    if let Some(new_path) = &app.wallpaper_data.new_path {
+                if ui.add_enabled(!busy, egui::Button::new("Change")).clicked() {
+                    if app.wallpaper_data.new_path.is_some() {
                         app.wallpaper_data.noselect = false;
-                        match change_wallpaper(new_path) {
-                            Ok(_) => {
-                                app.wallpaper_data.changerror = None;
-                                app.wallpaper_data.reloadneeded = Some(true);
+                        match picked_wallpaper(&app.wallpaper_data) {
+                            Ok(new_path) => {
+                                start_task(
+                                    &mut app.wallpaper_data,
+                                    ctx,
+                                    "Applying...",
+                                    move || {
+                                        TaskResult::Change(
+                                            change_wallpaper(&new_path).map(|_| new_path),
+                                        )
+                                    },
+                                );
                             }
                             Err(e) => {
-                                app.wallpaper_data.changerror = Some(e.to_string());
+                                app.wallpaper_data.changerror = Some(e);
                             }
                         }
                     } else {
@@ -319,6 +1041,131 @@ pub fn main(app: &mut MyApp, ctx: &egui::Context) {
                 if let Some(error) = app.wallpaper_data.changerror.clone() {
                     ui.label(RichText::new(format!("Failed to set Wallpaper: {error}")).size(20.0));
                 }
+
+                let binding = os_info::get();
+                let version = binding.version();
+                if version >= &os_info::Version::Semantic(10, 9, 0)
+                    && version < &os_info::Version::Semantic(14, 0, 0)
+                {
+                    if ui
+                        .add_enabled(
+                            !busy,
+                            egui::Button::new("Restore previous wallpaper database"),
+                        )
+                        .clicked()
+                    {
+                        app.wallpaper_data.restore_result = None;
+                        start_task(&mut app.wallpaper_data, ctx, "Restoring...", || {
+                            TaskResult::Restore(restore_wallpaper_database())
+                        });
+                    }
+
+                    match &app.wallpaper_data.restore_result {
+                        Some(Ok(message)) => {
+                            ui.label(message);
+                        }
+                        Some(Err(e)) => {
+                            ui.label(format!("Failed to restore: {e}"));
+                        }
+                        None => {}
+                    }
+                }
+
+                if !wallpaper_path.is_empty() {
+                    ui.collapsing("Placement", |ui| {
+                        show_placement(ui, &mut app.wallpaper_data, Path::new(&wallpaper_path));
+                    });
+                }
+
+                // The picked file, or the current wallpaper if nothing is picked
+                let inspect = app
+                    .wallpaper_data
+                    .new_path
+                    .clone()
+                    .unwrap_or(wallpaper_path.clone());
+                if !inspect.is_empty() {
+                    ui.collapsing("Dynamic wallpaper", |ui| {
+                        show_dynamic(ui, &mut app.wallpaper_data, &inspect);
+                    });
+                }
+
+                ui.collapsing("Gallery", |ui| {
+                    if let Some(clicked) =
+                        gallery::show_gallery(ui, &mut app.wallpaper_data.gallery, busy)
+                    {
+                        // Converting can take a while too
+                        start_task(&mut app.wallpaper_data, ctx, "Applying...", move || {
+                            TaskResult::Change(
+                                prepare_wallpaper(&clicked)
+                                    .and_then(|path| change_wallpaper(&path).map(|_| path)),
+                            )
+                        });
+                    }
+                });
+
+                ui.collapsing("Solid color or gradient", |ui| {
+                    if let Some((wallpaper, displays)) =
+                        generated::show_generator(ui, &mut app.wallpaper_data.generator, busy)
+                    {
+                        start_task(&mut app.wallpaper_data, ctx, "Rendering...", move || {
+                            TaskResult::Generated(generated::apply(&wallpaper, &displays))
+                        });
+                    }
+                });
+
+                ui.collapsing("Slideshow", |ui| {
+                    show_slideshow(ui, &mut app.wallpaper_data);
+                });
+
+                ui.collapsing("Desktops", |ui| {
+                    if app.wallpaper_data.desktops.is_none() {
+                        app.wallpaper_data.desktops = Some(get_desktops());
+                    }
+
+                    let mut changed = None;
+                    match app.wallpaper_data.desktops.as_ref().unwrap() {
+                        Ok(desktops) => {
+                            for desktop in desktops {
+                                ui.horizontal(|ui| {
+                                    ui.label(RichText::new(&desktop.name).strong());
+                                    ui.monospace(
+                                        desktop.wallpaper.as_deref().unwrap_or("Default Wallpaper"),
+                                    );
+                                    if app.wallpaper_data.new_path.is_some()
+                                        && ui
+                                            .add_enabled(
+                                                !busy,
+                                                egui::Button::new("Use picked file"),
+                                            )
+                                            .clicked()
+                                    {
+                                        changed = Some((
+                                            desktop.id.clone(),
+                                            picked_wallpaper(&app.wallpaper_data),
+                                        ));
+                                    }
+                                });
+                            }
+                        }
+                        Err(e) => {
+                            ui.label(format!("Failed to get desktops: {e}"));
+                        }
+                    }
+
+                    match changed {
+                        Some((desktop, Ok(new_path))) => {
+                            start_task(&mut app.wallpaper_data, ctx, "Applying...", move || {
+                                TaskResult::Change(
+                                    change_desktop_wallpaper(desktop, &new_path).map(|_| new_path),
+                                )
+                            });
+                        }
+                        Some((_, Err(e))) => {
+                            app.wallpaper_data.changerror = Some(e);
+                        }
+                        None => {}
+                    }
+                });
             }
         });
     });
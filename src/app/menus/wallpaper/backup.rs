@@ -0,0 +1,161 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Timestamped backups of desktoppicture.db, taken before every write
+
+use crate::app::helpers::app_support_dir;
+use log::info;
+use rusqlite::{Connection, MAIN_DB};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// How many backups to keep around
+const MAX_BACKUPS: usize = 10;
+
+pub fn default_dir() -> Result<PathBuf, String> {
+    Ok(app_support_dir()?.join("backups"))
+}
+
+/// The backups in `dir`, oldest first
+pub fn list_backups(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| e.to_string())?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| backup_time(path).is_some())
+        .collect();
+
+    backups.sort_by_key(|path| backup_time(path));
+    Ok(backups)
+}
+
+/// The time a backup was taken, from its file name (`desktoppicture-<unix millis>.db`)
+pub fn backup_time(path: &Path) -> Option<u128> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix("desktoppicture-")?
+        .strip_suffix(".db")?
+        .parse()
+        .ok()
+}
+
+/// Copies the database behind `conn` into a new backup in `dir`, dropping the oldest ones
+pub fn backup_database(conn: &Connection, dir: &Path) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_millis();
+    let path = dir.join(format!("desktoppicture-{now}.db"));
+
+    info!("Backing up desktoppicture.db to {}", path.display());
+    conn.backup(MAIN_DB, &path, None)
+        .map_err(|e| e.to_string())?;
+
+    let backups = list_backups(dir)?;
+    if backups.len() > MAX_BACKUPS {
+        for old in &backups[..backups.len() - MAX_BACKUPS] {
+            std::fs::remove_file(old).map_err(|e| e.to_string())?;
+        }
+    }
+
+    Ok(path)
+}
+
+/// Overwrites the database at `db` with the contents of `backup`
+pub fn restore_database(db: &Path, backup: &Path) -> Result<(), String> {
+    info!("Restoring {} from {}", db.display(), backup.display());
+
+    let mut conn = Connection::open(db).map_err(|e| e.to_string())?;
+    conn.restore(MAIN_DB, backup, None::<fn(rusqlite::backup::Progress)>)
+        .map_err(|e| e.to_string())?;
+    conn.close().map_err(|(_, e)| e.to_string())
+}
+
+/// Restores the newest backup in `dir` and removes it, so the next restore goes back one step further.
+/// Returns the restored backup's time.
+pub fn restore_latest(db: &Path, dir: &Path) -> Result<u128, String> {
+    let Some(latest) = list_backups(dir)?.pop() else {
+        return Err(String::from("There are no backups to restore"));
+    };
+
+    restore_database(db, &latest)?;
+    std::fs::remove_file(&latest).map_err(|e| e.to_string())?;
+
+    Ok(backup_time(&latest).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::desktoppicture::DesktopPictureDb;
+    use super::super::desktoppicture::tests::{TempDir, fixture_db};
+    use super::*;
+
+    fn wallpaper(db: &Path) -> Option<String> {
+        DesktopPictureDb::open(db)
+            .unwrap()
+            .current_wallpaper()
+            .unwrap()
+    }
+
+    #[test]
+    fn keeps_at_most_max_backups() {
+        let dir = TempDir::new("max-backups");
+        let conn = Connection::open(fixture_db(&dir, "/old.png")).unwrap();
+        let backups = dir.join("backups");
+
+        for _ in 0..MAX_BACKUPS + 3 {
+            backup_database(&conn, &backups).unwrap();
+            // Backups are named after the millisecond they were taken in
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        assert_eq!(list_backups(&backups).unwrap().len(), MAX_BACKUPS);
+    }
+
+    #[test]
+    fn restore_latest_restores_newest_and_removes_it() {
+        let dir = TempDir::new("restore-latest");
+        let db = fixture_db(&dir, "/first.png");
+        let backups = dir.join("backups");
+
+        for wallpaper in ["/second.png", "/third.png"] {
+            let conn = DesktopPictureDb::open(&db)
+                .unwrap()
+                .with_backups(backups.clone());
+            conn.write(|conn| conn.set_wallpaper_everywhere(wallpaper))
+                .unwrap();
+            conn.close().unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+        assert_eq!(wallpaper(&db).as_deref(), Some("/third.png"));
+
+        restore_latest(&db, &backups).unwrap();
+        assert_eq!(wallpaper(&db).as_deref(), Some("/second.png"));
+        assert_eq!(list_backups(&backups).unwrap().len(), 1);
+
+        restore_latest(&db, &backups).unwrap();
+        assert_eq!(wallpaper(&db).as_deref(), Some("/first.png"));
+        assert!(restore_latest(&db, &backups).is_err());
+    }
+}
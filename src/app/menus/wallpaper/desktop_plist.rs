@@ -0,0 +1,389 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Wallpapers before Mavericks live in ~/Library/Preferences/com.apple.desktop.plist:
+//   Background
+//     default        { ImageFilePath = ... }   Used by displays without their own entry
+//     <display id>   { ImageFilePath = ... }
+//     spaces         (Lion and Mountain Lion)
+//       <space uuid>
+//         default / <display id> { ImageFilePath = ... }
+
+use super::placement::{Placement, PlacementSettings, color_from_unit, color_to_unit};
+use crate::app::helpers::home_dir;
+use plist::{Dictionary, Value};
+use std::path::{Path, PathBuf};
+
+const IMAGE_FILE_PATH: &str = "ImageFilePath";
+// Also written by Lion and later, kept in sync if present
+const NEW_IMAGE_FILE_PATH: &str = "NewImageFilePath";
+// One of the strings from `Placement::plist_value`
+const PLACEMENT: &str = "Placement";
+// An array of three reals from 0 to 1
+const BACKGROUND_COLOR: &str = "BackgroundColor";
+
+pub fn default_path() -> Result<PathBuf, String> {
+    Ok(home_dir()?.join("Library/Preferences/com.apple.desktop.plist"))
+}
+
+pub fn load(path: &Path) -> Result<Value, String> {
+    if !path.exists() {
+        // Nothing was ever changed, so every display uses the default wallpaper
+        return Ok(Value::Dictionary(Dictionary::new()));
+    }
+    Value::from_file(path).map_err(|e| e.to_string())
+}
+
+pub fn save(path: &Path, plist: &Value) -> Result<(), String> {
+    plist.to_file_binary(path).map_err(|e| e.to_string())
+}
+
+/// Every display settings dictionary, named like "default" or "spaces/<uuid>/69677504"
+fn display_dicts(background: &Dictionary) -> Vec<(String, &Dictionary)> {
+    let mut dicts = Vec::new();
+
+    for (key, value) in background {
+        let Some(dict) = value.as_dictionary() else {
+            continue;
+        };
+        if key == "spaces" {
+            for (space, value) in dict {
+                let Some(space_dict) = value.as_dictionary() else {
+                    continue;
+                };
+                for (display, value) in space_dict {
+                    if let Some(display_dict) = value.as_dictionary() {
+                        dicts.push((format!("spaces/{space}/{display}"), display_dict));
+                    }
+                }
+            }
+        } else {
+            dicts.push((key.clone(), dict));
+        }
+    }
+
+    dicts
+}
+
+/// The wallpaper of every display (and Space) that has one
+pub fn wallpapers(plist: &Value) -> Vec<(String, String)> {
+    let Some(background) = plist
+        .as_dictionary()
+        .and_then(|root| root.get("Background"))
+        .and_then(Value::as_dictionary)
+    else {
+        return Vec::new();
+    };
+
+    display_dicts(background)
+        .into_iter()
+        .filter_map(|(name, dict)| {
+            dict.get(IMAGE_FILE_PATH)
+                .and_then(Value::as_string)
+                .map(|path| (name, path.to_string()))
+        })
+        .collect()
+}
+
+/// The wallpaper of the default display, or whatever is set if there is no default
+pub fn current_wallpaper(plist: &Value) -> Option<String> {
+    let wallpapers = wallpapers(plist);
+    wallpapers
+        .iter()
+        .find(|(name, _)| name == "default")
+        .or(wallpapers.first())
+        .map(|(_, path)| path.clone())
+}
+
+fn set_display_wallpaper(dict: &mut Dictionary, new_path: &str) {
+    dict.insert(IMAGE_FILE_PATH.into(), Value::String(new_path.into()));
+    if dict.contains_key(NEW_IMAGE_FILE_PATH) {
+        dict.insert(NEW_IMAGE_FILE_PATH.into(), Value::String(new_path.into()));
+    }
+}
+
+/// Sets the wallpaper of every display and Space
+pub fn set_wallpaper_everywhere(plist: &mut Value, new_path: &str) -> Result<(), String> {
+    let Some(root) = plist.as_dictionary_mut() else {
+        return Err(String::from("com.apple.desktop.plist is not a dictionary"));
+    };
+
+    if !root.contains_key("Background") {
+        root.insert("Background".into(), Value::Dictionary(Dictionary::new()));
+    }
+    let background = root.get_mut("Background").unwrap();
+    let Some(background) = background.as_dictionary_mut() else {
+        return Err(String::from("Background is not a dictionary"));
+    };
+
+    for (key, value) in background.iter_mut() {
+        let Some(dict) = value.as_dictionary_mut() else {
+            continue;
+        };
+        if key == "spaces" {
+            for space in dict.values_mut().filter_map(Value::as_dictionary_mut) {
+                for display in space.values_mut().filter_map(Value::as_dictionary_mut) {
+                    set_display_wallpaper(display, new_path);
+                }
+            }
+        } else {
+            set_display_wallpaper(dict, new_path);
+        }
+    }
+
+    // Displays without their own entry use the default
+    if !background.contains_key("default") {
+        let mut default = Dictionary::new();
+        set_display_wallpaper(&mut default, new_path);
+        background.insert("default".into(), Value::Dictionary(default));
+    }
+
+    Ok(())
+}
+
+/// Sets the wallpaper of one display, by the name `wallpapers` gave it
+pub fn set_wallpaper(plist: &mut Value, name: &str, new_path: &str) -> Result<(), String> {
+    let mut dict = plist
+        .as_dictionary_mut()
+        .and_then(|root| root.get_mut("Background"))
+        .and_then(Value::as_dictionary_mut);
+
+    let keys: Vec<&str> = match name.strip_prefix("spaces/") {
+        Some(rest) => match rest.split_once('/') {
+            Some((space, display)) => vec!["spaces", space, display],
+            None => return Err(format!("Invalid display name {name}")),
+        },
+        None => vec![name],
+    };
+    for key in keys {
+        dict = dict
+            .and_then(|dict| dict.get_mut(key))
+            .and_then(Value::as_dictionary_mut);
+    }
+
+    match dict {
+        Some(dict) => {
+            set_display_wallpaper(dict, new_path);
+            Ok(())
+        }
+        None => Err(format!("No display named {name}")),
+    }
+}
+
+// Colors are sometimes written as integers (0 or 1)
+fn as_unit(value: &Value) -> Option<f64> {
+    value
+        .as_real()
+        .or(value.as_signed_integer().map(|i| i as f64))
+}
+
+fn display_placement(dict: &Dictionary) -> PlacementSettings {
+    let mut settings = PlacementSettings::default();
+
+    if let Some(placement) = dict
+        .get(PLACEMENT)
+        .and_then(Value::as_string)
+        .and_then(Placement::from_plist_value)
+    {
+        settings.placement = placement;
+    }
+
+    if let Some(color) = dict.get(BACKGROUND_COLOR).and_then(Value::as_array)
+        && let [red, green, blue] = color.as_slice()
+        && let (Some(red), Some(green), Some(blue)) = (as_unit(red), as_unit(green), as_unit(blue))
+    {
+        settings.fill_color = color_from_unit([red, green, blue]);
+    }
+
+    settings
+}
+
+/// The placement of the default display, or whatever is set if there is no default
+pub fn current_placement(plist: &Value) -> PlacementSettings {
+    let Some(background) = plist
+        .as_dictionary()
+        .and_then(|root| root.get("Background"))
+        .and_then(Value::as_dictionary)
+    else {
+        return PlacementSettings::default();
+    };
+
+    let dicts = display_dicts(background);
+    dicts
+        .iter()
+        .find(|(name, _)| name == "default")
+        .or(dicts.first())
+        .map(|(_, dict)| display_placement(dict))
+        .unwrap_or_default()
+}
+
+fn set_display_placement(dict: &mut Dictionary, settings: &PlacementSettings) {
+    dict.insert(
+        PLACEMENT.into(),
+        Value::String(settings.placement.plist_value().into()),
+    );
+    dict.insert(
+        BACKGROUND_COLOR.into(),
+        Value::Array(
+            color_to_unit(settings.fill_color)
+                .into_iter()
+                .map(Value::Real)
+                .collect(),
+        ),
+    );
+}
+
+/// Sets the placement of every display and Space
+pub fn set_placement_everywhere(
+    plist: &mut Value,
+    settings: &PlacementSettings,
+) -> Result<(), String> {
+    let Some(root) = plist.as_dictionary_mut() else {
+        return Err(String::from("com.apple.desktop.plist is not a dictionary"));
+    };
+
+    if !root.contains_key("Background") {
+        root.insert("Background".into(), Value::Dictionary(Dictionary::new()));
+    }
+    let Some(background) = root.get_mut("Background").unwrap().as_dictionary_mut() else {
+        return Err(String::from("Background is not a dictionary"));
+    };
+
+    for (key, value) in background.iter_mut() {
+        let Some(dict) = value.as_dictionary_mut() else {
+            continue;
+        };
+        if key == "spaces" {
+            for space in dict.values_mut().filter_map(Value::as_dictionary_mut) {
+                for display in space.values_mut().filter_map(Value::as_dictionary_mut) {
+                    set_display_placement(display, settings);
+                }
+            }
+        } else {
+            set_display_placement(dict, settings);
+        }
+    }
+
+    if !background.contains_key("default") {
+        let mut default = Dictionary::new();
+        set_display_placement(&mut default, settings);
+        background.insert("default".into(), Value::Dictionary(default));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::desktoppicture::tests::TempDir;
+    use super::*;
+
+    fn display(path: &str) -> Value {
+        let mut dict = Dictionary::new();
+        dict.insert(IMAGE_FILE_PATH.into(), Value::String(path.into()));
+        Value::Dictionary(dict)
+    }
+
+    /// A `Background` with a default, a display of its own and a display in a Space
+    fn sample() -> Value {
+        let mut space = Dictionary::new();
+        space.insert("69677504".into(), display("/space.png"));
+        let mut spaces = Dictionary::new();
+        spaces.insert("ABCD-1234".into(), Value::Dictionary(space));
+
+        let mut background = Dictionary::new();
+        background.insert("default".into(), display("/default.png"));
+        background.insert("69677505".into(), display("/display.png"));
+        background.insert("spaces".into(), Value::Dictionary(spaces));
+
+        let mut root = Dictionary::new();
+        root.insert("Background".into(), Value::Dictionary(background));
+        Value::Dictionary(root)
+    }
+
+    /// Saves and loads the plist again, like the next launch would
+    fn round_trip(name: &str, plist: &Value) -> Value {
+        let dir = TempDir::new(name);
+        let path = dir.join("com.apple.desktop.plist");
+        save(&path, plist).unwrap();
+        load(&path).unwrap()
+    }
+
+    fn sorted(mut wallpapers: Vec<(String, String)>) -> Vec<(String, String)> {
+        wallpapers.sort();
+        wallpapers
+    }
+
+    fn pair(name: &str, path: &str) -> (String, String) {
+        (name.to_string(), path.to_string())
+    }
+
+    #[test]
+    fn wallpapers_round_trip() {
+        let plist = round_trip("plist-wallpapers", &sample());
+
+        assert_eq!(
+            sorted(wallpapers(&plist)),
+            vec![
+                pair("69677505", "/display.png"),
+                pair("default", "/default.png"),
+                pair("spaces/ABCD-1234/69677504", "/space.png"),
+            ]
+        );
+        assert_eq!(current_wallpaper(&plist).as_deref(), Some("/default.png"));
+    }
+
+    #[test]
+    fn set_wallpaper_everywhere_round_trip() {
+        let mut plist = sample();
+        set_wallpaper_everywhere(&mut plist, "/new.png").unwrap();
+        let plist = round_trip("plist-everywhere", &plist);
+
+        let wallpapers = wallpapers(&plist);
+        assert_eq!(wallpapers.len(), 3);
+        assert!(wallpapers.iter().all(|(_, path)| path == "/new.png"));
+    }
+
+    #[test]
+    fn set_wallpaper_everywhere_on_empty_plist() {
+        let dir = TempDir::new("plist-missing");
+        let mut plist = load(&dir.join("missing.plist")).unwrap();
+        set_wallpaper_everywhere(&mut plist, "/new.png").unwrap();
+        let plist = round_trip("plist-empty", &plist);
+
+        assert_eq!(wallpapers(&plist), vec![pair("default", "/new.png")]);
+    }
+
+    #[test]
+    fn set_wallpaper_round_trip() {
+        let mut plist = sample();
+        set_wallpaper(&mut plist, "spaces/ABCD-1234/69677504", "/new.png").unwrap();
+        let plist = round_trip("plist-one", &plist);
+
+        assert_eq!(
+            sorted(wallpapers(&plist)),
+            vec![
+                pair("69677505", "/display.png"),
+                pair("default", "/default.png"),
+                pair("spaces/ABCD-1234/69677504", "/new.png"),
+            ]
+        );
+        assert!(set_wallpaper(&mut sample(), "12345", "/new.png").is_err());
+        assert!(set_wallpaper(&mut sample(), "spaces/ABCD-1234", "/new.png").is_err());
+    }
+}
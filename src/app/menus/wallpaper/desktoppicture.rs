@@ -0,0 +1,545 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// A model of the Dock's desktoppicture.db (Mavericks to Ventura).
+// This is only possible thanks to the amazing reverse engineering work done over here. Give them a star.
+// https://github.com/tech-otaku/macos-desktop
+//
+// The tables are:
+//   displays(display_uuid)
+//   spaces(space_uuid)
+//   pictures(space_id, display_id)        A desktop: a Space on a display. NULL ids are the defaults
+//   preferences(key, data_id, picture_id) A setting of a desktop
+//   data(value)                           The values the preferences point to
+
+use super::backup;
+use super::placement::{Placement, PlacementSettings, color_from_unit, color_to_unit};
+use crate::app::helpers::home_dir;
+use rusqlite::{Connection, OptionalExtension};
+use std::path::{Path, PathBuf};
+
+/// Preference key for the picture's file path (relative to `KEY_FOLDER` if that is set)
+pub const KEY_IMAGE_PATH: i64 = 1;
+/// Preference key for how the picture is placed, see `Placement::db_value`
+pub const KEY_PLACEMENT: i64 = 2;
+/// Preference keys for the fill color, as reals from 0 to 1
+pub const KEY_FILL_RED: i64 = 3;
+pub const KEY_FILL_GREEN: i64 = 4;
+pub const KEY_FILL_BLUE: i64 = 5;
+/// Preference key for the folder the picture is in
+pub const KEY_FOLDER: i64 = 10;
+
+pub struct Display {
+    pub id: i64,
+    pub uuid: String,
+}
+
+pub struct Space {
+    pub id: i64,
+    pub uuid: String,
+}
+
+pub struct Picture {
+    pub id: i64,
+    // None means the picture applies to every Space/display
+    pub space_id: Option<i64>,
+    pub display_id: Option<i64>,
+}
+
+pub struct DesktopPictureDb {
+    conn: Connection,
+    // Where to back the database up to before writing. No backups if None
+    backup_dir: Option<PathBuf>,
+}
+
+pub fn default_path() -> Result<PathBuf, String> {
+    Ok(home_dir()?.join("Library/Application Support/Dock/desktoppicture.db"))
+}
+
+/// The value that shows up most often. Ties go to the one that came first
+fn most_common<T: PartialEq>(values: impl IntoIterator<Item = T>) -> Option<T> {
+    let mut counts: Vec<(T, usize)> = Vec::new();
+    for value in values {
+        match counts.iter_mut().find(|(other, _)| *other == value) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((value, 1)),
+        }
+    }
+
+    let max = counts.iter().map(|(_, count)| *count).max()?;
+    counts
+        .into_iter()
+        .find(|(_, count)| *count == max)
+        .map(|(value, _)| value)
+}
+
+fn short_uuid(uuid: &str) -> &str {
+    uuid.split('-').next().unwrap_or(uuid)
+}
+
+impl DesktopPictureDb {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Err(String::from("Database file not found :("));
+        }
+
+        match Connection::open(path) {
+            Ok(conn) => Ok(Self {
+                conn,
+                backup_dir: None,
+            }),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Opens the user's database, backing it up before every write
+    pub fn open_default() -> Result<Self, String> {
+        Ok(Self::open(&default_path()?)?.with_backups(backup::default_dir()?))
+    }
+
+    pub fn with_backups(mut self, dir: PathBuf) -> Self {
+        self.backup_dir = Some(dir);
+        self
+    }
+
+    /// Runs `f` inside a transaction, after taking a backup.
+    /// Nothing `f` did is kept if it fails.
+    pub fn write<T>(&self, f: impl FnOnce(&Self) -> Result<T, String>) -> Result<T, String> {
+        if let Some(dir) = &self.backup_dir {
+            backup::backup_database(&self.conn, dir)?;
+        }
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| e.to_string())?;
+        let res = f(self)?;
+        tx.commit().map_err(|e| e.to_string())?;
+
+        Ok(res)
+    }
+
+    pub fn displays(&self) -> Result<Vec<Display>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT rowid, display_uuid FROM displays ORDER BY rowid")
+            .map_err(|e| e.to_string())?;
+
+        stmt.query_map([], |row| {
+            Ok(Display {
+                id: row.get(0)?,
+                uuid: row.get(1)?,
+            })
+        })
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string())
+    }
+
+    pub fn spaces(&self) -> Result<Vec<Space>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT rowid, space_uuid FROM spaces ORDER BY rowid")
+            .map_err(|e| e.to_string())?;
+
+        stmt.query_map([], |row| {
+            Ok(Space {
+                id: row.get(0)?,
+                uuid: row.get(1)?,
+            })
+        })
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string())
+    }
+
+    pub fn pictures(&self) -> Result<Vec<Picture>, String> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT rowid, space_id, display_id FROM pictures ORDER BY rowid")
+            .map_err(|e| e.to_string())?;
+
+        stmt.query_map([], |row| {
+            Ok(Picture {
+                id: row.get(0)?,
+                space_id: row.get(1)?,
+                display_id: row.get(2)?,
+            })
+        })
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string())
+    }
+
+    /// Reads a preference of a picture as text
+    pub fn preference(&self, picture_id: i64, key: i64) -> Result<Option<String>, String> {
+        self.conn
+            .query_row(
+                "SELECT cast(data.value as text) FROM preferences \
+                 JOIN data ON data.rowid = preferences.data_id \
+                 WHERE preferences.picture_id = ?1 AND preferences.key = ?2",
+                [picture_id, key],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())
+    }
+
+    /// Sets a preference of a picture, replacing the old value.
+    /// Should be called inside `write`
+    pub fn set_preference(
+        &self,
+        picture_id: i64,
+        key: i64,
+        value: &dyn rusqlite::ToSql,
+    ) -> Result<(), String> {
+        self.conn
+            .execute("INSERT INTO data(value) VALUES (?1)", [value])
+            .map_err(|e| e.to_string())?;
+        let data_id = self.conn.last_insert_rowid();
+
+        self.conn
+            .execute(
+                "DELETE FROM preferences WHERE picture_id = ?1 AND key = ?2",
+                [picture_id, key],
+            )
+            .map_err(|e| e.to_string())?;
+        self.conn
+            .execute(
+                "INSERT INTO preferences(key, data_id, picture_id) VALUES (?1, ?2, ?3)",
+                [key, data_id, picture_id],
+            )
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    /// Removes a preference from a picture
+    pub fn clear_preference(&self, picture_id: i64, key: i64) -> Result<(), String> {
+        self.conn
+            .execute(
+                "DELETE FROM preferences WHERE picture_id = ?1 AND key = ?2",
+                [picture_id, key],
+            )
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Deletes values no preference points to anymore
+    pub fn remove_orphaned_data(&self) -> Result<(), String> {
+        self.conn
+            .execute(
+                "DELETE FROM data WHERE rowid NOT IN (SELECT data_id FROM preferences)",
+                (),
+            )
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    /// The full path of the wallpaper of a picture, if it has one
+    pub fn wallpaper(&self, picture_id: i64) -> Result<Option<String>, String> {
+        let Some(path) = self.preference(picture_id, KEY_IMAGE_PATH)? else {
+            return Ok(None);
+        };
+
+        // Pictures picked from a folder only store the file name
+        match self.preference(picture_id, KEY_FOLDER)? {
+            Some(folder) if !path.starts_with('/') => {
+                Ok(Some(PathBuf::from(folder).join(path).display().to_string()))
+            }
+            _ => Ok(Some(path)),
+        }
+    }
+
+    /// Should be called inside `write`
+    pub fn set_wallpaper(&self, picture_id: i64, path: &str) -> Result<(), String> {
+        self.clear_preference(picture_id, KEY_FOLDER)?;
+        self.set_preference(picture_id, KEY_IMAGE_PATH, &path)?;
+        self.remove_orphaned_data()
+    }
+
+    /// Adds the picture every display and Space falls back to.
+    /// Should be called inside `write`
+    pub fn add_default_picture(&self) -> Result<Picture, String> {
+        self.conn
+            .execute(
+                "INSERT INTO pictures(space_id, display_id) VALUES (NULL, NULL)",
+                (),
+            )
+            .map_err(|e| e.to_string())?;
+
+        Ok(Picture {
+            id: self.conn.last_insert_rowid(),
+            space_id: None,
+            display_id: None,
+        })
+    }
+
+    /// Sets the wallpaper of every display and Space.
+    /// Should be called inside `write`
+    pub fn set_wallpaper_everywhere(&self, path: &str) -> Result<(), String> {
+        let mut pictures = self.pictures()?;
+        // A fresh database has no pictures yet, so there would be nothing to set
+        if pictures.is_empty() {
+            pictures.push(self.add_default_picture()?);
+        }
+
+        for picture in pictures {
+            self.set_wallpaper(picture.id, path)?;
+        }
+        Ok(())
+    }
+
+    /// The wallpaper most desktops show
+    pub fn current_wallpaper(&self) -> Result<Option<String>, String> {
+        let mut wallpapers = Vec::new();
+        for picture in self.pictures()? {
+            if let Some(wallpaper) = self.wallpaper(picture.id)? {
+                wallpapers.push(wallpaper);
+            }
+        }
+        Ok(most_common(wallpapers))
+    }
+
+    /// How a picture is placed and the color around it. Unset values are the macOS defaults
+    pub fn placement(&self, picture_id: i64) -> Result<PlacementSettings, String> {
+        let mut settings = PlacementSettings::default();
+
+        if let Some(value) = self.preference(picture_id, KEY_PLACEMENT)?
+            && let Some(placement) = value.parse().ok().and_then(Placement::from_db_value)
+        {
+            settings.placement = placement;
+        }
+
+        let mut color = [0.0; 3];
+        let mut has_color = false;
+        for (i, key) in [KEY_FILL_RED, KEY_FILL_GREEN, KEY_FILL_BLUE]
+            .into_iter()
+            .enumerate()
+        {
+            if let Some(value) = self.preference(picture_id, key)? {
+                color[i] = value
+                    .parse()
+                    .map_err(|e| format!("Invalid fill color {value:?}: {e}"))?;
+                has_color = true;
+            }
+        }
+        if has_color {
+            settings.fill_color = color_from_unit(color);
+        }
+
+        Ok(settings)
+    }
+
+    /// Should be called inside `write`
+    pub fn set_placement(
+        &self,
+        picture_id: i64,
+        settings: &PlacementSettings,
+    ) -> Result<(), String> {
+        self.set_preference(picture_id, KEY_PLACEMENT, &settings.placement.db_value())?;
+
+        let color = color_to_unit(settings.fill_color);
+        for (i, key) in [KEY_FILL_RED, KEY_FILL_GREEN, KEY_FILL_BLUE]
+            .into_iter()
+            .enumerate()
+        {
+            self.set_preference(picture_id, key, &color[i])?;
+        }
+        self.remove_orphaned_data()
+    }
+
+    /// Sets the placement of every display and Space.
+    /// Should be called inside `write`
+    pub fn set_placement_everywhere(&self, settings: &PlacementSettings) -> Result<(), String> {
+        for picture in self.pictures()? {
+            self.set_placement(picture.id, settings)?;
+        }
+        Ok(())
+    }
+
+    /// The placement most desktops use
+    pub fn current_placement(&self) -> Result<PlacementSettings, String> {
+        let placements = self
+            .pictures()?
+            .iter()
+            .map(|picture| self.placement(picture.id))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(most_common(placements).unwrap_or_default())
+    }
+
+    /// A readable name for a picture, like "Display 1 (37D8832A), Space 2 (B1A3C2D4)"
+    pub fn describe(picture: &Picture, displays: &[Display], spaces: &[Space]) -> String {
+        let display = match picture.display_id {
+            Some(id) => match displays.iter().position(|display| display.id == id) {
+                Some(index) => format!(
+                    "Display {} ({})",
+                    index + 1,
+                    short_uuid(&displays[index].uuid)
+                ),
+                None => format!("Unknown display {id}"),
+            },
+            None => String::from("All displays"),
+        };
+        let space = match picture.space_id {
+            Some(id) => match spaces.iter().position(|space| space.id == id) {
+                Some(index) => format!("Space {} ({})", index + 1, short_uuid(&spaces[index].uuid)),
+                None => format!("Unknown Space {id}"),
+            },
+            None => String::from("all Spaces"),
+        };
+        format!("{display}, {space}")
+    }
+
+    pub fn close(self) -> Result<(), String> {
+        self.conn.close().map_err(|(_, e)| e.to_string())
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// A fresh directory for a test's files, removed when dropped
+    pub struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("prefsuite-test-{}-{name}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl std::ops::Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A database like the Dock's, with one default picture showing `wallpaper`
+    pub fn fixture_db(dir: &Path, wallpaper: &str) -> PathBuf {
+        let path = dir.join("desktoppicture.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE displays (display_uuid VARCHAR);
+             CREATE TABLE spaces (space_uuid VARCHAR DEFAULT NULL);
+             CREATE TABLE pictures (space_id INTEGER, display_id INTEGER);
+             CREATE TABLE preferences (key INTEGER, data_id INTEGER, picture_id INTEGER);
+             CREATE TABLE data (value);
+             INSERT INTO pictures (space_id, display_id) VALUES (NULL, NULL);",
+        )
+        .unwrap();
+        conn.execute("INSERT INTO data (value) VALUES (?1)", [wallpaper])
+            .unwrap();
+        conn.execute(
+            "INSERT INTO preferences (key, data_id, picture_id) VALUES (1, 1, 1)",
+            (),
+        )
+        .unwrap();
+        path
+    }
+
+    #[test]
+    fn failed_write_rolls_back() {
+        let dir = TempDir::new("rollback");
+        let db = DesktopPictureDb::open(&fixture_db(&dir, "/old.png")).unwrap();
+
+        let res: Result<(), String> = db.write(|db| {
+            db.set_wallpaper_everywhere("/new.png")?;
+            Err(String::from("Something went wrong"))
+        });
+
+        assert!(res.is_err());
+        assert_eq!(db.current_wallpaper().unwrap().as_deref(), Some("/old.png"));
+    }
+
+    #[test]
+    fn write_takes_a_backup() {
+        let dir = TempDir::new("write-backup");
+        let db = DesktopPictureDb::open(&fixture_db(&dir, "/old.png"))
+            .unwrap()
+            .with_backups(dir.join("backups"));
+
+        db.write(|db| db.set_wallpaper_everywhere("/new.png"))
+            .unwrap();
+
+        assert_eq!(db.current_wallpaper().unwrap().as_deref(), Some("/new.png"));
+        assert_eq!(backup::list_backups(&dir.join("backups")).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn set_wallpaper_everywhere_without_pictures() {
+        let dir = TempDir::new("no-pictures");
+        let db = DesktopPictureDb::open(&fixture_db(&dir, "/old.png")).unwrap();
+        db.conn.execute("DELETE FROM pictures", ()).unwrap();
+
+        db.write(|db| db.set_wallpaper_everywhere("/new.png"))
+            .unwrap();
+
+        assert_eq!(db.pictures().unwrap().len(), 1);
+        assert_eq!(db.current_wallpaper().unwrap().as_deref(), Some("/new.png"));
+    }
+
+    #[test]
+    fn most_common_value() {
+        assert_eq!(most_common(Vec::<u8>::new()), None);
+        assert_eq!(most_common([1, 2, 2, 3]), Some(2));
+        // Ties go to the first one
+        assert_eq!(most_common([3, 1, 1, 3]), Some(3));
+    }
+
+    #[test]
+    fn current_values_are_the_most_common() {
+        let dir = TempDir::new("most-common");
+        let db = DesktopPictureDb::open(&fixture_db(&dir, "/default.png")).unwrap();
+        let fit = PlacementSettings {
+            placement: Placement::Fit,
+            ..Default::default()
+        };
+
+        db.write(|db| {
+            for display in 1..=2 {
+                db.conn
+                    .execute(
+                        "INSERT INTO pictures (space_id, display_id) VALUES (NULL, ?1)",
+                        [display],
+                    )
+                    .map_err(|e| e.to_string())?;
+                let id = db.conn.last_insert_rowid();
+                db.set_wallpaper(id, "/other.png")?;
+                db.set_placement(id, &fit)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(
+            db.current_wallpaper().unwrap().as_deref(),
+            Some("/other.png")
+        );
+        assert!(db.current_placement().unwrap() == fit);
+    }
+}
@@ -0,0 +1,105 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Restarting the Dock, which only rereads desktoppicture.db / com.apple.desktop.plist when it starts.
+// launchd relaunches it right after it's killed.
+// Waiting for the relaunch takes a while, so the GUI only calls this from background threads.
+
+use log::{info, warn};
+use std::ffi::OsStr;
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, ProcessRefreshKind, ProcessesToUpdate, System, Uid, UpdateKind};
+
+const DOCK: &str = "Dock";
+// How long to wait for launchd to bring the Dock back
+const RELAUNCH_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+// Only names and owners are refreshed, which is much cheaper than `new_all`
+fn refresh_kind() -> ProcessRefreshKind {
+    ProcessRefreshKind::nothing().with_user(UpdateKind::OnlyIfNotSet)
+}
+
+/// The user PrefSuite runs as
+fn current_uid(system: &mut System) -> Result<Uid, String> {
+    let pid = sysinfo::get_current_pid().map_err(String::from)?;
+    system.refresh_processes_specifics(ProcessesToUpdate::Some(&[pid]), false, refresh_kind());
+    system
+        .process(pid)
+        .and_then(|process| process.user_id())
+        .cloned()
+        .ok_or_else(|| String::from("Could not find out the current user"))
+}
+
+/// The pids of the user's Docks. Other logged in users (fast user switching) have their own,
+/// which can't be killed and don't show this user's wallpaper anyway
+fn dock_pids(system: &mut System, uid: &Uid) -> Vec<Pid> {
+    system.refresh_processes_specifics(ProcessesToUpdate::All, true, refresh_kind());
+    system
+        .processes_by_exact_name(OsStr::new(DOCK))
+        .filter(|process| process.user_id() == Some(uid))
+        .map(|process| process.pid())
+        .collect()
+}
+
+/// Kills the user's Dock and waits for it to come back. Blocks for up to `RELAUNCH_TIMEOUT`
+pub fn restart() -> Result<(), String> {
+    let mut system = System::new();
+    let uid = current_uid(&mut system)?;
+
+    let old = dock_pids(&mut system, &uid);
+    if old.is_empty() {
+        return Err(String::from("The Dock is not running"));
+    }
+
+    for pid in &old {
+        let killed = system.process(*pid).is_some_and(|process| process.kill());
+        if !killed {
+            return Err(format!("Could not kill the Dock (pid {pid})"));
+        }
+    }
+    info!("Killed the Dock ({old:?}), waiting for it to relaunch");
+
+    let start = Instant::now();
+    while start.elapsed() < RELAUNCH_TIMEOUT {
+        std::thread::sleep(POLL_INTERVAL);
+        if dock_pids(&mut system, &uid)
+            .iter()
+            .any(|pid| !old.contains(pid))
+        {
+            info!("Dock relaunched after {:?}", start.elapsed());
+            return Ok(());
+        }
+    }
+
+    warn!("The Dock did not relaunch within {RELAUNCH_TIMEOUT:?}");
+    Err(format!(
+        "The Dock did not come back within {} seconds",
+        RELAUNCH_TIMEOUT.as_secs()
+    ))
+}
+
+/// Restarts the Dock after a successful write that needs it, so it picks the change up
+pub fn restart_after<T>(needed: bool, res: Result<T, String>) -> Result<T, String> {
+    let value = res?;
+    if needed {
+        restart()
+            .map_err(|e| format!("The change was saved, but restarting the Dock failed: {e}"))?;
+    }
+    Ok(value)
+}
@@ -0,0 +1,441 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Dynamic wallpapers are HEIC files with several frames, and XMP metadata saying when to show each.
+// The metadata is a base64 binary plist in one of these properties:
+//   apple_desktop:solar  { ap = { d, l }, si = [ { a = altitude, z = azimuth, i = frame }, ... ] }
+//   apple_desktop:h24    { ap = { d, l }, ti = [ { t = fraction of the day, i = frame }, ... ] }
+//   apple_desktop:apr    { d = dark frame, l = light frame }
+// None of this needs macOS, the file is only read
+
+use super::slideshow;
+use base64::Engine;
+use plist::{Dictionary, Value};
+use std::io::Read;
+use std::path::Path;
+
+const XMP_START: &[u8] = b"<x:xmpmeta";
+const XMP_END: &[u8] = b"</x:xmpmeta>";
+// Files are read in chunks, system dynamic wallpapers can be over 100 MB
+const CHUNK_SIZE: usize = 1 << 20;
+// XMP packets are a few KB, anything this big isn't one
+const MAX_XMP_SIZE: usize = 1 << 20;
+
+/// Which frames are used for light and dark mode
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Appearance {
+    pub light: u64,
+    pub dark: u64,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SolarEntry {
+    pub frame: u64,
+    // Of the sun, in degrees
+    pub altitude: f64,
+    pub azimuth: f64,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TimeEntry {
+    pub frame: u64,
+    pub minute_of_day: u32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum DynamicSchedule {
+    // Follows the position of the sun
+    Solar {
+        appearance: Option<Appearance>,
+        entries: Vec<SolarEntry>,
+    },
+    // Follows the time of day
+    Time {
+        appearance: Option<Appearance>,
+        // Sorted by time
+        entries: Vec<TimeEntry>,
+    },
+    // Only switches with light/dark mode
+    Appearance(Appearance),
+}
+
+/// Jumps between occurrences of the needle's first byte instead of comparing at every position
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    let mut pos = 0;
+    while let Some(offset) = haystack[pos..].iter().position(|&b| b == needle[0]) {
+        pos += offset;
+        if haystack[pos..].starts_with(needle) {
+            return Some(pos);
+        }
+        pos += 1;
+    }
+    None
+}
+
+/// The XMP packet embedded in a file, if there is one.
+/// Only the packet is kept in memory, the rest is read in chunks and dropped
+pub fn read_xmp(mut reader: impl Read) -> Result<Option<String>, String> {
+    let mut buffer = Vec::new();
+    let mut chunk = vec![0; CHUNK_SIZE];
+    // Whether `buffer` starts with the packet
+    let mut found_start = false;
+    // Where to continue looking for the end. Markers can be split between chunks
+    let mut end_from = 0;
+
+    loop {
+        let read = reader.read(&mut chunk).map_err(|e| e.to_string())?;
+        if read == 0 {
+            return Ok(None);
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+
+        if !found_start {
+            match find(&buffer, XMP_START) {
+                Some(start) => {
+                    buffer.drain(..start);
+                    found_start = true;
+                }
+                None => {
+                    // Only keep what could be the beginning of a split marker
+                    buffer.drain(..buffer.len().saturating_sub(XMP_START.len() - 1));
+                    continue;
+                }
+            }
+        }
+
+        if let Some(end) = find(&buffer[end_from..], XMP_END) {
+            buffer.truncate(end_from + end + XMP_END.len());
+            return String::from_utf8(buffer)
+                .map(Some)
+                .map_err(|e| e.to_string());
+        }
+        if buffer.len() > MAX_XMP_SIZE {
+            return Err(String::from("The XMP metadata is too large"));
+        }
+        end_from = buffer.len().saturating_sub(XMP_END.len() - 1);
+    }
+}
+
+/// The value of an `apple_desktop:` property, written either as an attribute or an element
+fn xmp_property<'a>(xmp: &'a str, name: &str) -> Option<&'a str> {
+    let attribute = format!("apple_desktop:{name}=\"");
+    if let Some(start) = xmp.find(&attribute) {
+        let rest = &xmp[start + attribute.len()..];
+        return rest.find('"').map(|end| &rest[..end]);
+    }
+
+    let element = format!("<apple_desktop:{name}>");
+    let start = xmp.find(&element)? + element.len();
+    let rest = &xmp[start..];
+    rest.find("</").map(|end| rest[..end].trim())
+}
+
+fn decode_property(value: &str) -> Result<Dictionary, String> {
+    // Long values are sometimes wrapped
+    let value: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(value)
+        .map_err(|e| e.to_string())?;
+
+    match Value::from_reader(std::io::Cursor::new(bytes)).map_err(|e| e.to_string())? {
+        Value::Dictionary(dict) => Ok(dict),
+        other => Err(format!("Expected a dictionary, got {other:?}")),
+    }
+}
+
+fn number(dict: &Dictionary, key: &str) -> Option<f64> {
+    match dict.get(key)? {
+        Value::Real(r) => Some(*r),
+        Value::Integer(i) => i.as_signed().map(|i| i as f64),
+        _ => None,
+    }
+}
+
+fn frame(dict: &Dictionary, key: &str) -> Option<u64> {
+    dict.get(key)?.as_unsigned_integer()
+}
+
+fn appearance(dict: &Dictionary) -> Option<Appearance> {
+    Some(Appearance {
+        light: frame(dict, "l")?,
+        dark: frame(dict, "d")?,
+    })
+}
+
+fn entries<'a>(dict: &'a Dictionary, key: &str) -> Result<Vec<&'a Dictionary>, String> {
+    let Some(array) = dict.get(key).and_then(Value::as_array) else {
+        return Err(format!("Missing {key}"));
+    };
+    array
+        .iter()
+        .map(|entry| {
+            entry
+                .as_dictionary()
+                .ok_or_else(|| format!("Invalid {key} entry: {entry:?}"))
+        })
+        .collect()
+}
+
+fn solar_entry(entry: &Dictionary) -> Option<SolarEntry> {
+    Some(SolarEntry {
+        frame: frame(entry, "i")?,
+        altitude: number(entry, "a")?,
+        azimuth: number(entry, "z")?,
+    })
+}
+
+fn time_entry(entry: &Dictionary) -> Option<TimeEntry> {
+    let fraction = number(entry, "t")?.clamp(0.0, 1.0);
+    Some(TimeEntry {
+        frame: frame(entry, "i")?,
+        minute_of_day: (fraction * 1440.0).round() as u32 % 1440,
+    })
+}
+
+fn parse_solar(dict: &Dictionary) -> Result<DynamicSchedule, String> {
+    let entries = entries(dict, "si")?
+        .into_iter()
+        .map(|entry| solar_entry(entry).ok_or_else(|| format!("Invalid solar entry: {entry:?}")))
+        .collect::<Result<_, _>>()?;
+
+    Ok(DynamicSchedule::Solar {
+        appearance: dict
+            .get("ap")
+            .and_then(Value::as_dictionary)
+            .and_then(appearance),
+        entries,
+    })
+}
+
+fn parse_h24(dict: &Dictionary) -> Result<DynamicSchedule, String> {
+    let mut entries = entries(dict, "ti")?
+        .into_iter()
+        .map(|entry| time_entry(entry).ok_or_else(|| format!("Invalid time entry: {entry:?}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.minute_of_day);
+
+    Ok(DynamicSchedule::Time {
+        appearance: dict
+            .get("ap")
+            .and_then(Value::as_dictionary)
+            .and_then(appearance),
+        entries,
+    })
+}
+
+/// Every schedule in an XMP packet
+pub fn parse_xmp(xmp: &str) -> Result<Vec<DynamicSchedule>, String> {
+    let mut schedules = Vec::new();
+    if let Some(solar) = xmp_property(xmp, "solar") {
+        schedules.push(parse_solar(&decode_property(solar)?)?);
+    }
+    if let Some(h24) = xmp_property(xmp, "h24") {
+        schedules.push(parse_h24(&decode_property(h24)?)?);
+    }
+    if let Some(apr) = xmp_property(xmp, "apr") {
+        match appearance(&decode_property(apr)?) {
+            Some(appearance) => schedules.push(DynamicSchedule::Appearance(appearance)),
+            None => return Err(String::from("Invalid apr metadata")),
+        }
+    }
+    Ok(schedules)
+}
+
+/// Every schedule in a file's metadata. Empty if it isn't a dynamic wallpaper
+pub fn parse_dynamic(reader: impl Read) -> Result<Vec<DynamicSchedule>, String> {
+    match read_xmp(reader)? {
+        Some(xmp) => parse_xmp(&xmp),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Slow for big files, see `read_xmp`
+pub fn read_dynamic(path: &Path) -> Result<Vec<DynamicSchedule>, String> {
+    parse_dynamic(std::fs::File::open(path).map_err(|e| e.to_string())?)
+}
+
+/// The frame a time schedule shows at a time of day
+pub fn frame_at(entries: &[TimeEntry], minute_of_day: u32) -> Option<u64> {
+    slideshow::entry_at(entries, minute_of_day, |entry| entry.minute_of_day)
+        .map(|entry| entry.frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dict(entries: &[(&str, Value)]) -> Value {
+        Value::Dictionary(
+            entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect(),
+        )
+    }
+
+    fn int(i: u64) -> Value {
+        Value::Integer(i.into())
+    }
+
+    /// Encodes a property the way Apple's tools do: a base64 binary plist
+    fn encode(value: &Value) -> String {
+        let mut bytes = Vec::new();
+        value.to_writer_binary(&mut bytes).unwrap();
+        base64::engine::general_purpose::STANDARD.encode(bytes)
+    }
+
+    fn appearance_dict() -> Value {
+        dict(&[("l", int(0)), ("d", int(1))])
+    }
+
+    /// A file with solar and h24 as attributes and apr as an element, surrounded by other data
+    fn sample_file() -> Vec<u8> {
+        let solar = dict(&[
+            ("ap", appearance_dict()),
+            (
+                "si",
+                Value::Array(vec![
+                    dict(&[
+                        ("a", Value::Real(-10.5)),
+                        ("z", Value::Real(90.0)),
+                        ("i", int(1)),
+                    ]),
+                    dict(&[("a", Value::Real(45.0)), ("z", int(180)), ("i", int(0))]),
+                ]),
+            ),
+        ]);
+        let h24 = dict(&[
+            ("ap", appearance_dict()),
+            (
+                "ti",
+                Value::Array(vec![
+                    dict(&[("t", Value::Real(0.75)), ("i", int(1))]),
+                    dict(&[("t", Value::Real(0.25)), ("i", int(0))]),
+                ]),
+            ),
+        ]);
+
+        let xmp = format!(
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF><rdf:Description \
+             apple_desktop:solar=\"{}\" apple_desktop:h24=\"{}\">\
+             <apple_desktop:apr>\n{}\n</apple_desktop:apr>\
+             </rdf:Description></rdf:RDF></x:xmpmeta>",
+            encode(&solar),
+            encode(&h24),
+            encode(&appearance_dict()),
+        );
+
+        let mut file = b"\0\0\0\x18ftypheic\xff\xfe".to_vec();
+        file.extend_from_slice(xmp.as_bytes());
+        file.extend_from_slice(b"\0mdat\xff\xff");
+        file
+    }
+
+    const APPEARANCE: Option<Appearance> = Some(Appearance { light: 0, dark: 1 });
+
+    #[test]
+    fn parses_every_property() {
+        let schedules = parse_dynamic(sample_file().as_slice()).unwrap();
+
+        assert_eq!(
+            schedules,
+            vec![
+                DynamicSchedule::Solar {
+                    appearance: APPEARANCE,
+                    entries: vec![
+                        SolarEntry {
+                            frame: 1,
+                            altitude: -10.5,
+                            azimuth: 90.0,
+                        },
+                        SolarEntry {
+                            frame: 0,
+                            altitude: 45.0,
+                            azimuth: 180.0,
+                        },
+                    ],
+                },
+                DynamicSchedule::Time {
+                    appearance: APPEARANCE,
+                    entries: vec![
+                        TimeEntry {
+                            frame: 0,
+                            minute_of_day: 360,
+                        },
+                        TimeEntry {
+                            frame: 1,
+                            minute_of_day: 1080,
+                        },
+                    ],
+                },
+                DynamicSchedule::Appearance(APPEARANCE.unwrap()),
+            ]
+        );
+    }
+
+    #[test]
+    fn xmp_split_between_chunks() {
+        let sample = sample_file();
+        let end = find(&sample, XMP_END).unwrap();
+
+        // Splits the start marker, then the end marker
+        for split in [1, XMP_START.len() / 2, end + 1, end + XMP_END.len() / 2] {
+            let mut file = vec![0; CHUNK_SIZE - split];
+            file.extend(&sample);
+            assert_eq!(parse_dynamic(file.as_slice()).unwrap().len(), 3);
+        }
+    }
+
+    #[test]
+    fn not_dynamic() {
+        assert_eq!(parse_dynamic(&b"\0\0\0\x18ftypheic"[..]).unwrap(), vec![]);
+        // Started but never finished
+        assert_eq!(
+            parse_dynamic(&b"<x:xmpmeta apple_desktop:apr"[..]).unwrap(),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn invalid_property() {
+        let file = b"<x:xmpmeta apple_desktop:apr=\"not base64!\"></x:xmpmeta>";
+        assert!(parse_dynamic(&file[..]).is_err());
+    }
+
+    #[test]
+    fn frame_at_wraps_around() {
+        let entries = [
+            TimeEntry {
+                frame: 0,
+                minute_of_day: 360,
+            },
+            TimeEntry {
+                frame: 1,
+                minute_of_day: 1080,
+            },
+        ];
+
+        // Before the first entry is still yesterday's last
+        assert_eq!(frame_at(&entries, 0), Some(1));
+        assert_eq!(frame_at(&entries, 359), Some(1));
+        assert_eq!(frame_at(&entries, 360), Some(0));
+        assert_eq!(frame_at(&entries, 1079), Some(0));
+        assert_eq!(frame_at(&entries, 1439), Some(1));
+        assert_eq!(frame_at(&[], 720), None);
+    }
+}
@@ -0,0 +1,387 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Wallpaper gallery: thumbnails of the system wallpapers and user folders, plus recent wallpapers
+
+use crate::app::helpers::{app_support_dir, cache_dir};
+use log::{error, info};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, channel};
+
+use eframe::egui;
+use eframe::egui::RichText;
+
+const SYSTEM_WALLPAPER_DIRS: &[&str] = &[
+    "/System/Library/Desktop Pictures",
+    "/Library/Desktop Pictures",
+];
+// Longest side of a thumbnail, in pixels
+const THUMBNAIL_SIZE: u32 = 256;
+// How big thumbnails are shown
+const THUMBNAIL_DISPLAY_SIZE: f32 = 128.0;
+// How many recent wallpapers to remember
+const MAX_HISTORY: usize = 20;
+
+// A scanned folder and the images in it
+type GalleryFolder = (String, Result<Vec<PathBuf>, String>);
+
+/// The user's gallery folders and recently applied wallpapers
+#[derive(Default)]
+pub struct GallerySettings {
+    pub folders: Vec<String>,
+    // Newest first
+    pub history: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct GalleryData {
+    settings: Option<Result<GallerySettings, String>>,
+    // Every image found, by folder
+    images: Option<Vec<GalleryFolder>>,
+    // Generated thumbnails. Missing means it's still being generated
+    thumbnails: HashMap<PathBuf, Result<PathBuf, String>>,
+    // Results from the thumbnail thread
+    receiver: Option<Receiver<(PathBuf, Result<PathBuf, String>)>>,
+}
+
+fn settings_path() -> Result<PathBuf, String> {
+    Ok(app_support_dir()?.join("gallery.json"))
+}
+
+impl GallerySettings {
+    pub fn load() -> Result<Self, String> {
+        let path = settings_path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let value = json::parse(&contents).map_err(|e| e.to_string())?;
+
+        let strings = |key: &str| {
+            value[key]
+                .members()
+                .filter_map(|member| member.as_str().map(str::to_string))
+                .collect()
+        };
+        Ok(Self {
+            folders: strings("folders"),
+            history: strings("history"),
+        })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = settings_path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+
+        let value = json::object! {
+            folders: self.folders.clone(),
+            history: self.history.clone(),
+        };
+        std::fs::write(path, value.pretty(4)).map_err(|e| e.to_string())
+    }
+
+    /// Moves (or adds) a wallpaper to the front of the history
+    pub fn remember(&mut self, wallpaper: &str) {
+        self.history.retain(|old| old != wallpaper);
+        self.history.insert(0, wallpaper.to_string());
+        self.history.truncate(MAX_HISTORY);
+    }
+}
+
+/// Generates a thumbnail with the image crate, falling back to sips for formats it can't read (like HEIC)
+pub fn make_thumbnail(image: &Path, out: &Path) -> Result<(), String> {
+    match image::open(image) {
+        Ok(decoded) => decoded
+            .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+            .save(out)
+            .map_err(|e| e.to_string()),
+        Err(e) => {
+            info!("image can't read {}, trying sips: {e}", image.display());
+
+            let status = std::process::Command::new("sips")
+                .arg("-Z")
+                .arg(THUMBNAIL_SIZE.to_string())
+                .arg("-s")
+                .arg("format")
+                .arg("png")
+                .arg(image)
+                .arg("--out")
+                .arg(out)
+                .output()
+                .map_err(|e| e.to_string())?
+                .status;
+
+            if status.success() && out.exists() {
+                Ok(())
+            } else {
+                Err(format!("Could not make a thumbnail of {}", image.display()))
+            }
+        }
+    }
+}
+
+/// The cached thumbnail of an image, generating it if needed
+pub fn cached_thumbnail(cache: &Path, image: &Path) -> Result<PathBuf, String> {
+    let thumbnail = super::cached_file_path(cache, image)?;
+    if !thumbnail.exists() {
+        std::fs::create_dir_all(cache).map_err(|e| e.to_string())?;
+        make_thumbnail(image, &thumbnail)?;
+    }
+    Ok(thumbnail)
+}
+
+impl GalleryData {
+    fn scan(&mut self, ctx: &egui::Context, folders: &[String]) {
+        let mut images = Vec::new();
+        for folder in SYSTEM_WALLPAPER_DIRS
+            .iter()
+            .map(|dir| dir.to_string())
+            .chain(folders.iter().cloned())
+        {
+            let path = Path::new(&folder);
+            if !path.exists() {
+                continue;
+            }
+            images.push((folder.clone(), super::images_in(path)));
+        }
+
+        let todo: Vec<PathBuf> = images
+            .iter()
+            .filter_map(|(_, images)| images.as_ref().ok())
+            .flatten()
+            .filter(|image| !self.thumbnails.contains_key(*image))
+            .cloned()
+            .collect();
+
+        let (sender, receiver) = channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let cache = match cache_dir() {
+                Ok(cache) => cache.join("thumbnails"),
+                Err(e) => {
+                    error!("No thumbnail cache: {e}");
+                    return;
+                }
+            };
+
+            for image in todo {
+                let thumbnail = cached_thumbnail(&cache, &image);
+                // The gallery was closed or rescanned
+                if sender.send((image, thumbnail)).is_err() {
+                    return;
+                }
+                ctx.request_repaint();
+            }
+        });
+
+        self.images = Some(images);
+        self.receiver = Some(receiver);
+    }
+
+    fn receive_thumbnails(&mut self) {
+        if let Some(receiver) = &self.receiver {
+            for (image, thumbnail) in receiver.try_iter() {
+                self.thumbnails.insert(image, thumbnail);
+            }
+        }
+    }
+
+    /// Forgets the history so it's reloaded, e.g. after a wallpaper was applied
+    pub fn reload_history(&mut self) {
+        self.settings = None;
+    }
+
+    /// Adds a wallpaper to the saved history. Only called from the UI thread, which also saves
+    /// the folders, so one can't overwrite the other.
+    /// Failing to save is not worth failing the change over
+    pub fn record_history(&mut self, wallpaper: &str) {
+        if self.settings.is_none() {
+            self.settings = Some(GallerySettings::load());
+        }
+
+        let res = match self.settings.as_mut().unwrap() {
+            Ok(settings) => {
+                settings.remember(wallpaper);
+                settings.save()
+            }
+            Err(e) => Err(e.clone()),
+        };
+        if let Err(e) = res {
+            error!("Failed to save wallpaper history: {e}");
+        }
+    }
+}
+
+fn thumbnail_button(
+    ui: &mut egui::Ui,
+    image: &Path,
+    thumbnail: Option<&Result<PathBuf, String>>,
+) -> bool {
+    let size = egui::vec2(THUMBNAIL_DISPLAY_SIZE, THUMBNAIL_DISPLAY_SIZE);
+    let name = image
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let response = match thumbnail {
+        Some(Ok(thumbnail)) => ui.add(egui::Button::image(
+            egui::Image::new(format!("file://{}", thumbnail.display())).fit_to_exact_size(size),
+        )),
+        Some(Err(_)) => ui.add_sized(size, egui::Button::new(&name)),
+        None => ui.add_sized(size, egui::Spinner::new()),
+    };
+
+    response
+        .on_hover_text(image.display().to_string())
+        .clicked()
+}
+
+/// Shows the gallery. Returns the wallpaper that was clicked, if any.
+/// `busy` disables the wallpapers while another wallpaper task runs
+pub fn show_gallery(ui: &mut egui::Ui, data: &mut GalleryData, busy: bool) -> Option<String> {
+    if data.settings.is_none() {
+        data.settings = Some(GallerySettings::load());
+    }
+    if data.images.is_none() {
+        let folders = match data.settings.as_ref().unwrap() {
+            Ok(settings) => settings.folders.clone(),
+            Err(_) => Vec::new(),
+        };
+        data.scan(ui.ctx(), &folders);
+    }
+    data.receive_thumbnails();
+
+    let settings = match data.settings.as_mut().unwrap() {
+        Ok(settings) => settings,
+        Err(e) => {
+            ui.label(format!("Failed to load gallery settings: {e}"));
+            return None;
+        }
+    };
+
+    let mut clicked = None;
+
+    if !settings.history.is_empty() {
+        ui.label(RichText::new("Recent:").heading());
+        for wallpaper in &settings.history {
+            if ui.add_enabled(!busy, egui::Link::new(wallpaper)).clicked() {
+                clicked = Some(wallpaper.clone());
+            }
+        }
+        ui.add_space(10.0);
+    }
+
+    let mut rescan = false;
+    ui.horizontal(|ui| {
+        if ui.button("Add folder").clicked()
+            && let Some(folder) = rfd::FileDialog::new().pick_folder()
+        {
+            settings.folders.push(folder.display().to_string());
+            if let Err(e) = settings.save() {
+                error!("Failed to save gallery settings: {e}");
+            }
+            rescan = true;
+        }
+        if ui.button("Rescan").clicked() {
+            rescan = true;
+        }
+    });
+
+    let mut remove = None;
+    for (folder, images) in data.images.as_ref().unwrap() {
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(folder).strong());
+            if let Some(index) = settings.folders.iter().position(|f| f == folder)
+                && ui.button("Remove").clicked()
+            {
+                remove = Some(index);
+            }
+        });
+
+        match images {
+            Ok(images) => {
+                ui.horizontal_wrapped(|ui| {
+                    if busy {
+                        ui.disable();
+                    }
+                    for image in images {
+                        if thumbnail_button(ui, image, data.thumbnails.get(image)) {
+                            clicked = Some(image.display().to_string());
+                        }
+                    }
+                });
+            }
+            Err(e) => {
+                ui.label(format!("Failed to read folder: {e}"));
+            }
+        }
+    }
+
+    if let Some(index) = remove {
+        settings.folders.remove(index);
+        if let Err(e) = settings.save() {
+            error!("Failed to save gallery settings: {e}");
+        }
+        rescan = true;
+    }
+    if rescan {
+        data.images = None;
+    }
+
+    clicked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remember_puts_newest_first() {
+        let mut settings = GallerySettings::default();
+        settings.remember("/a.png");
+        settings.remember("/b.png");
+
+        assert_eq!(settings.history, ["/b.png", "/a.png"]);
+    }
+
+    #[test]
+    fn remember_moves_duplicates_to_the_front() {
+        let mut settings = GallerySettings::default();
+        for wallpaper in ["/a.png", "/b.png", "/c.png", "/a.png"] {
+            settings.remember(wallpaper);
+        }
+
+        assert_eq!(settings.history, ["/a.png", "/c.png", "/b.png"]);
+    }
+
+    #[test]
+    fn remember_keeps_at_most_max_history() {
+        let mut settings = GallerySettings::default();
+        for i in 0..MAX_HISTORY + 5 {
+            settings.remember(&format!("/{i}.png"));
+        }
+
+        assert_eq!(settings.history.len(), MAX_HISTORY);
+        assert_eq!(settings.history[0], format!("/{}.png", MAX_HISTORY + 4));
+        assert_eq!(settings.history[MAX_HISTORY - 1], "/5.png");
+    }
+}
@@ -0,0 +1,478 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Solid color and gradient wallpapers, rendered at the resolution of each display
+
+use crate::app::applescript::{self, Value};
+use crate::app::helpers::app_support_dir;
+use image::{Rgb, RgbImage};
+use log::info;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, channel};
+
+use eframe::egui;
+
+// Used when the display resolution can't be read
+const FALLBACK_RESOLUTION: (u32, u32) = (1920, 1080);
+// The most noise can change a channel by
+const MAX_NOISE: f32 = 32.0;
+
+#[derive(Clone, Copy, PartialEq, Hash, Default)]
+pub enum GeneratedKind {
+    #[default]
+    Solid,
+    Gradient,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct GeneratedWallpaper {
+    pub kind: GeneratedKind,
+    // The solid color, or the gradient stops in order
+    pub colors: [[u8; 3]; 3],
+    // Whether the gradient has a middle stop
+    pub three_stops: bool,
+    // Direction of the gradient, clockwise from left-to-right
+    pub angle: f32,
+    // 0 to 1
+    pub noise: f32,
+}
+
+impl Default for GeneratedWallpaper {
+    fn default() -> Self {
+        Self {
+            kind: GeneratedKind::Solid,
+            colors: [[30, 30, 30], [40, 70, 120], [200, 120, 80]],
+            three_stops: false,
+            angle: 90.0,
+            noise: 0.0,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct DisplayInfo {
+    // The CGDirectDisplayID
+    pub id: u32,
+    // Like "Color LCD" or "LG HDR 4K"
+    pub name: String,
+    // In pixels
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Default)]
+pub struct GeneratorData {
+    pub wallpaper: GeneratedWallpaper,
+    // The connected displays
+    displays: Option<Result<Vec<DisplayInfo>, String>>,
+    // The displays being read on a background thread
+    displays_lookup: Option<Receiver<Result<Vec<DisplayInfo>, String>>>,
+    // Result of the last apply
+    message: Option<Result<String, String>>,
+}
+
+impl GeneratorData {
+    /// Shows the result of an apply started from `show_generator`
+    pub fn applied(&mut self, res: Result<String, String>) {
+        self.message = Some(res);
+    }
+}
+
+// {{id, name, width, height}, ...} for every screen. The frame is in points, so it's scaled up to
+// pixels, which is what Retina displays want. `frame()` comes back as a record on older macOS
+// and as a list on newer, `as list` handles both
+const DISPLAYS_SCRIPT: &str = "use framework \"AppKit\"
+set displayList to {}
+repeat with screen in current application's NSScreen's screens()
+    set screenNumber to ((screen's deviceDescription())'s objectForKey:\"NSScreenNumber\") as integer
+    set screenName to \"\"
+    try
+        set screenName to (screen's localizedName()) as text
+    end try
+    set scale to (screen's backingScaleFactor()) as real
+    set frameSize to (item 2 of ((screen's frame()) as list)) as list
+    set end of displayList to {screenNumber, screenName, ((item 1 of frameSize) * scale) as integer, ((item 2 of frameSize) * scale) as integer}
+end repeat
+return displayList";
+
+/// Parses the output of `DISPLAYS_SCRIPT`
+fn parse_displays(value: &Value) -> Result<Vec<DisplayInfo>, String> {
+    let Some(screens) = value.as_list() else {
+        return Err(format!("Unexpected displays: {value}"));
+    };
+
+    let mut displays = Vec::new();
+    for screen in screens {
+        let number = |value: &Value| value.as_integer().and_then(|n| u32::try_from(n).ok());
+        let Some([id, name, width, height]) = screen.as_list() else {
+            return Err(format!("Unexpected display: {screen}"));
+        };
+        let (Some(id), Some(name), Some(width), Some(height)) =
+            (number(id), name.as_str(), number(width), number(height))
+        else {
+            return Err(format!("Unexpected display: {screen}"));
+        };
+        displays.push(DisplayInfo {
+            id,
+            name: name.to_string(),
+            width,
+            height,
+        });
+    }
+
+    if displays.is_empty() {
+        return Err(String::from("No displays found"));
+    }
+    Ok(displays)
+}
+
+/// Every connected display, in the order NSScreen lists them
+pub fn displays() -> Result<Vec<DisplayInfo>, String> {
+    parse_displays(&applescript::run(DISPLAYS_SCRIPT)?)
+}
+
+/// Runs `displays` on a background thread, starting AppleScriptObjC takes a moment
+fn lookup_displays(ctx: &egui::Context) -> Receiver<Result<Vec<DisplayInfo>, String>> {
+    let (sender, receiver) = channel();
+    let ctx = ctx.clone();
+
+    std::thread::spawn(move || {
+        // Nobody is waiting anymore if this fails
+        let _ = sender.send(displays());
+        ctx.request_repaint();
+    });
+
+    receiver
+}
+
+fn lerp(a: [u8; 3], b: [u8; 3], t: f32) -> [f32; 3] {
+    [0, 1, 2].map(|i| a[i] as f32 + (b[i] as f32 - a[i] as f32) * t)
+}
+
+impl GeneratedWallpaper {
+    /// The color at `t` (0 to 1) along the gradient
+    fn color_at(&self, t: f32) -> [f32; 3] {
+        if self.kind == GeneratedKind::Solid {
+            return self.colors[0].map(|c| c as f32);
+        }
+
+        if !self.three_stops {
+            lerp(self.colors[0], self.colors[1], t)
+        } else if t < 0.5 {
+            lerp(self.colors[0], self.colors[1], t * 2.0)
+        } else {
+            lerp(self.colors[1], self.colors[2], (t - 0.5) * 2.0)
+        }
+    }
+
+    pub fn render(&self, width: u32, height: u32) -> RgbImage {
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        // Project the corners onto the gradient direction so it spans the whole image
+        let corners = [
+            (0.0, 0.0),
+            (width as f32, 0.0),
+            (0.0, height as f32),
+            (width as f32, height as f32),
+        ];
+        let projections = corners.map(|(x, y)| x * cos + y * sin);
+        let min = projections.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = projections
+            .iter()
+            .cloned()
+            .fold(f32::NEG_INFINITY, f32::max);
+        let span = (max - min).max(1.0);
+
+        // xorshift, seeded the same every time so rendering is reproducible
+        let mut seed: u32 = 0x9e37_79b9;
+
+        RgbImage::from_fn(width, height, |x, y| {
+            let t = ((x as f32 * cos + y as f32 * sin) - min) / span;
+            let mut color = self.color_at(t.clamp(0.0, 1.0));
+
+            if self.noise > 0.0 {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let offset = (seed as f32 / u32::MAX as f32 - 0.5) * 2.0 * MAX_NOISE * self.noise;
+                color = color.map(|c| c + offset);
+            }
+
+            Rgb(color.map(|c| c.round().clamp(0.0, 255.0) as u8))
+        })
+    }
+
+    /// A file name unique to these settings, so the Dock doesn't keep showing an old one
+    fn file_name(&self, width: u32, height: u32) -> String {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.kind.hash(&mut hasher);
+        self.colors.hash(&mut hasher);
+        self.three_stops.hash(&mut hasher);
+        self.angle.to_bits().hash(&mut hasher);
+        self.noise.to_bits().hash(&mut hasher);
+
+        format!("{:016x}-{width}x{height}.png", hasher.finish())
+    }
+
+    /// Renders and saves the wallpaper at a resolution, reusing an earlier render
+    pub fn save(&self, width: u32, height: u32) -> Result<PathBuf, String> {
+        let dir = app_support_dir()?.join("generated");
+        let path = dir.join(self.file_name(width, height));
+
+        if !path.exists() {
+            std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+            info!("Rendering {}", path.display());
+            self.render(width, height)
+                .save(&path)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(path)
+    }
+}
+
+/// Which display a desktop is on, matched by display id. None when the desktop's backend
+/// doesn't know its display id, or the display isn't connected
+fn display_of(desktop: &super::Desktop, displays: &[DisplayInfo]) -> Option<usize> {
+    let id = desktop.display_id?;
+    displays.iter().position(|display| display.id == id)
+}
+
+/// Renders the wallpaper for every display and sets each desktop to the render for its display.
+/// Desktops that can't be matched to a display get the largest render.
+/// Slow (a 6K render takes seconds), so it runs as a wallpaper task
+pub fn apply(wallpaper: &GeneratedWallpaper, displays: &[DisplayInfo]) -> Result<String, String> {
+    let mut renders = Vec::new();
+    for display in displays {
+        renders.push(
+            wallpaper
+                .save(display.width, display.height)?
+                .display()
+                .to_string(),
+        );
+    }
+
+    let Some(largest) = displays
+        .iter()
+        .zip(&renders)
+        .max_by_key(|(display, _)| display.width * display.height)
+        .map(|(_, render)| render.clone())
+    else {
+        return Err(String::from("No displays to render for"));
+    };
+
+    let desktops = super::get_desktops()?;
+    if desktops.is_empty() {
+        super::change_wallpaper(&largest)?;
+        return Ok(format!("Applied {largest}"));
+    }
+
+    let changes: Vec<_> = desktops
+        .into_iter()
+        .map(|desktop| {
+            let render = match display_of(&desktop, displays) {
+                Some(index) => renders[index].clone(),
+                None => largest.clone(),
+            };
+            (desktop.id, render)
+        })
+        .collect();
+    super::change_desktop_wallpapers(&changes)?;
+
+    Ok(format!(
+        "Applied to {} desktop(s) at {}",
+        changes.len(),
+        displays
+            .iter()
+            .map(|display| format!("{}x{}", display.width, display.height))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
+
+fn color_row(ui: &mut egui::Ui, label: &str, color: &mut [u8; 3]) {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        ui.color_edit_button_srgb(color);
+    });
+}
+
+/// Shows the generator. Returns the wallpaper and the displays to render it for when Apply is
+/// clicked. `busy` disables Apply while another wallpaper task runs
+pub fn show_generator(
+    ui: &mut egui::Ui,
+    data: &mut GeneratorData,
+    busy: bool,
+) -> Option<(GeneratedWallpaper, Vec<DisplayInfo>)> {
+    if data.displays.is_none() && data.displays_lookup.is_none() {
+        data.displays_lookup = Some(lookup_displays(ui.ctx()));
+    }
+    if let Some(lookup) = &data.displays_lookup
+        && let Ok(res) = lookup.try_recv()
+    {
+        data.displays = Some(res);
+        data.displays_lookup = None;
+    }
+
+    let wallpaper = &mut data.wallpaper;
+    ui.horizontal(|ui| {
+        ui.radio_value(&mut wallpaper.kind, GeneratedKind::Solid, "Solid color");
+        ui.radio_value(&mut wallpaper.kind, GeneratedKind::Gradient, "Gradient");
+    });
+
+    match wallpaper.kind {
+        GeneratedKind::Solid => {
+            color_row(ui, "Color:", &mut wallpaper.colors[0]);
+        }
+        GeneratedKind::Gradient => {
+            ui.checkbox(&mut wallpaper.three_stops, "Three colors");
+            color_row(ui, "Start:", &mut wallpaper.colors[0]);
+            if wallpaper.three_stops {
+                color_row(ui, "Middle:", &mut wallpaper.colors[1]);
+                color_row(ui, "End:", &mut wallpaper.colors[2]);
+            } else {
+                color_row(ui, "End:", &mut wallpaper.colors[1]);
+            }
+            ui.horizontal(|ui| {
+                ui.label("Angle:");
+                ui.add(egui::Slider::new(&mut wallpaper.angle, 0.0..=360.0).suffix("°"));
+            });
+        }
+    }
+    ui.horizontal(|ui| {
+        ui.label("Noise:");
+        ui.add(egui::Slider::new(&mut wallpaper.noise, 0.0..=1.0));
+    });
+
+    let Some(displays) = &data.displays else {
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label("Reading displays...");
+        });
+        return None;
+    };
+    let displays = match displays {
+        Ok(displays) => displays.clone(),
+        Err(e) => {
+            ui.label(format!(
+                "Failed to get display resolutions, using {}x{}: {e}",
+                FALLBACK_RESOLUTION.0, FALLBACK_RESOLUTION.1
+            ));
+            vec![DisplayInfo {
+                id: 0,
+                name: String::new(),
+                width: FALLBACK_RESOLUTION.0,
+                height: FALLBACK_RESOLUTION.1,
+            }]
+        }
+    };
+    ui.label(format!(
+        "Displays: {}",
+        displays
+            .iter()
+            .map(|display| format!("{} ({}x{})", display.name, display.width, display.height))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ));
+
+    let mut apply = None;
+    if ui.add_enabled(!busy, egui::Button::new("Apply")).clicked() {
+        data.message = None;
+        apply = Some((data.wallpaper.clone(), displays));
+    }
+
+    match &data.message {
+        Some(Ok(message)) => {
+            ui.label(message);
+        }
+        Some(Err(e)) => {
+            ui.label(format!("Failed to apply: {e}"));
+        }
+        None => {}
+    }
+
+    apply
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Desktop, DesktopId};
+    use super::*;
+
+    fn desktop(display_id: Option<u32>) -> Desktop {
+        Desktop {
+            id: DesktopId::SystemEvents(1),
+            name: String::from("LG HDR 4K"),
+            wallpaper: None,
+            display_id,
+        }
+    }
+
+    fn test_displays() -> Vec<DisplayInfo> {
+        parse_displays(
+            &applescript::parse(
+                "{{1, \"Built-in Retina Display\", 2880, 1800}, {69733382, \"LG HDR 4K\", 3840, 2160}}",
+            )
+            .unwrap(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_displays() {
+        let displays: Vec<_> = test_displays()
+            .iter()
+            .map(|display| {
+                (
+                    display.id,
+                    display.name.clone(),
+                    display.width,
+                    display.height,
+                )
+            })
+            .collect();
+        assert_eq!(
+            displays,
+            [
+                (1, String::from("Built-in Retina Display"), 2880, 1800),
+                (69733382, String::from("LG HDR 4K"), 3840, 2160),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_unexpected_displays() {
+        for output in [
+            "{}",
+            "\"display\"",
+            "{{1, \"Display\", 2880}}",
+            "{{-1, \"Display\", 2880, 1800}}",
+        ] {
+            assert!(parse_displays(&applescript::parse(output).unwrap()).is_err());
+        }
+    }
+
+    #[test]
+    fn matches_displays_by_id() {
+        let displays = test_displays();
+        assert_eq!(display_of(&desktop(Some(69733382)), &displays), Some(1));
+        assert_eq!(display_of(&desktop(Some(1)), &displays), Some(0));
+        // Not matched by name or position, so these get the largest render
+        assert_eq!(display_of(&desktop(Some(5)), &displays), None);
+        assert_eq!(display_of(&desktop(None), &displays), None);
+    }
+}
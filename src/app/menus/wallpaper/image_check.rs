@@ -0,0 +1,204 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Checking that a file is an image macOS can use as a wallpaper, and converting it if not
+
+use crate::app::helpers::cache_dir;
+use image::{ImageFormat, ImageReader};
+use log::info;
+use std::path::{Path, PathBuf};
+
+// Formats the image crate can't decode, but macOS (and so sips) can
+const SIPS_ONLY_EXTENSIONS: &[&str] = &["heic", "heif", "avif"];
+// Of those, the ones macOS can use as a wallpaper
+const SIPS_WALLPAPER_EXTENSIONS: &[&str] = &["heic", "heif"];
+
+#[derive(Clone, PartialEq)]
+pub enum ImageCheck {
+    // Can be used as is
+    Ok,
+    // A valid image macOS can't use as a wallpaper. Holds the format name
+    NeedsConversion(String),
+    // Not an image, or a corrupt one
+    Invalid(String),
+}
+
+/// Whether macOS can show this format as a wallpaper
+fn is_wallpaper_format(format: ImageFormat) -> bool {
+    matches!(
+        format,
+        ImageFormat::Png
+            | ImageFormat::Jpeg
+            | ImageFormat::Tiff
+            | ImageFormat::Gif
+            | ImageFormat::Bmp
+    )
+}
+
+fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Asks sips whether it can read the image
+fn sips_can_read(path: &Path) -> bool {
+    std::process::Command::new("sips")
+        .arg("-g")
+        .arg("pixelWidth")
+        .arg(path)
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+pub fn check_image(path: &Path) -> ImageCheck {
+    if !path.exists() {
+        return ImageCheck::Invalid(format!("{} does not exist", path.display()));
+    }
+
+    let reader = match ImageReader::open(path).and_then(|reader| reader.with_guessed_format()) {
+        Ok(reader) => reader,
+        Err(e) => return ImageCheck::Invalid(e.to_string()),
+    };
+    let format = reader.format();
+
+    match reader.decode() {
+        Ok(_) => match format {
+            Some(format) if is_wallpaper_format(format) => ImageCheck::Ok,
+            Some(format) => ImageCheck::NeedsConversion(format!("{format:?}")),
+            None => ImageCheck::Invalid(String::from("Unknown image format")),
+        },
+        Err(e) => {
+            let ext = extension(path);
+            if !SIPS_ONLY_EXTENSIONS.contains(&ext.as_str()) {
+                return ImageCheck::Invalid(e.to_string());
+            }
+
+            if !sips_can_read(path) {
+                ImageCheck::Invalid(format!("{} is not a valid {ext} image", path.display()))
+            } else if SIPS_WALLPAPER_EXTENSIONS.contains(&ext.as_str()) {
+                ImageCheck::Ok
+            } else {
+                ImageCheck::NeedsConversion(ext.to_uppercase())
+            }
+        }
+    }
+}
+
+/// Converts an image to a PNG, with sips as a fallback for formats the image crate can't read
+pub fn convert_to_png(image: &Path, out: &Path) -> Result<(), String> {
+    info!("Converting {} to {}", image.display(), out.display());
+
+    match image::open(image) {
+        Ok(decoded) => decoded
+            .save_with_format(out, ImageFormat::Png)
+            .map_err(|e| e.to_string()),
+        Err(_) => {
+            let status = std::process::Command::new("sips")
+                .arg("-s")
+                .arg("format")
+                .arg("png")
+                .arg(image)
+                .arg("--out")
+                .arg(out)
+                .output()
+                .map_err(|e| e.to_string())?
+                .status;
+
+            if status.success() && out.exists() {
+                Ok(())
+            } else {
+                Err(format!("Could not convert {}", image.display()))
+            }
+        }
+    }
+}
+
+/// Converts an image into the application cache, reusing an earlier conversion
+pub fn cached_conversion(image: &Path) -> Result<PathBuf, String> {
+    let cache = cache_dir()?.join("converted");
+    let out = super::cached_file_path(&cache, image)?;
+
+    if !out.exists() {
+        std::fs::create_dir_all(&cache).map_err(|e| e.to_string())?;
+        convert_to_png(image, &out)?;
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::desktoppicture::tests::TempDir;
+    use super::*;
+
+    fn write_image(path: &Path) {
+        image::RgbImage::from_pixel(4, 2, image::Rgb([28, 58, 100]))
+            .save(path)
+            .unwrap();
+    }
+
+    #[test]
+    fn wallpaper_formats_are_ok() {
+        let dir = TempDir::new("check-ok");
+        for name in ["a.png", "a.jpg", "a.tiff", "a.bmp"] {
+            write_image(&dir.join(name));
+            assert!(check_image(&dir.join(name)) == ImageCheck::Ok, "{name}");
+        }
+    }
+
+    #[test]
+    fn other_formats_need_conversion() {
+        let dir = TempDir::new("check-convert");
+        let tga = dir.join("a.tga");
+        write_image(&tga);
+
+        assert!(check_image(&tga) == ImageCheck::NeedsConversion(String::from("Tga")));
+
+        let png = dir.join("converted.png");
+        convert_to_png(&tga, &png).unwrap();
+        assert!(check_image(&png) == ImageCheck::Ok);
+        assert_eq!(image::image_dimensions(&png).unwrap(), (4, 2));
+    }
+
+    #[test]
+    fn corrupt_and_missing_files_are_invalid() {
+        let dir = TempDir::new("check-invalid");
+
+        // A PNG signature followed by garbage
+        let corrupt = dir.join("corrupt.png");
+        std::fs::write(&corrupt, b"\x89PNG\r\n\x1a\nnot really a png").unwrap();
+        assert!(matches!(check_image(&corrupt), ImageCheck::Invalid(_)));
+
+        // A truncated image
+        let truncated = dir.join("truncated.jpg");
+        write_image(&truncated);
+        let bytes = std::fs::read(&truncated).unwrap();
+        std::fs::write(&truncated, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(matches!(check_image(&truncated), ImageCheck::Invalid(_)));
+
+        let text = dir.join("notes.jpg");
+        std::fs::write(&text, "just text").unwrap();
+        assert!(matches!(check_image(&text), ImageCheck::Invalid(_)));
+
+        assert!(matches!(
+            check_image(&dir.join("missing.png")),
+            ImageCheck::Invalid(_)
+        ));
+    }
+}
@@ -0,0 +1,319 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// How the wallpaper is scaled on the screen, and the color shown around it
+
+use crate::app::applescript::{self, Value};
+use std::path::Path;
+
+use eframe::egui;
+use eframe::egui::{Color32, Rect, pos2, vec2};
+
+// Aspect ratio of the preview screen
+const PREVIEW_ASPECT: f32 = 16.0 / 10.0;
+const PREVIEW_WIDTH: f32 = 320.0;
+// Smallest tile drawn in the preview, in points. Tiny images would otherwise be millions of tiles
+const MIN_PREVIEW_TILE: f32 = 4.0;
+
+// NSImageScaling values used by NSWorkspace's desktop image options
+const NS_SCALE_AXES_INDEPENDENTLY: i64 = 1;
+const NS_SCALE_NONE: i64 = 2;
+const NS_SCALE_PROPORTIONALLY_UP_OR_DOWN: i64 = 3;
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Placement {
+    // Scaled to cover the screen, cropping the edges
+    #[default]
+    Fill,
+    // Scaled to fit inside the screen, with the fill color around it
+    Fit,
+    // Scaled to the screen, ignoring the aspect ratio
+    Stretch,
+    // Unscaled in the middle of the screen
+    Center,
+    // Unscaled and repeated
+    Tile,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct PlacementSettings {
+    pub placement: Placement,
+    // sRGB
+    pub fill_color: [u8; 3],
+}
+
+impl Default for PlacementSettings {
+    fn default() -> Self {
+        Self {
+            placement: Placement::Fill,
+            // The dark blue macOS uses when no color was picked
+            fill_color: [28, 58, 100],
+        }
+    }
+}
+
+impl Placement {
+    pub const ALL: [Placement; 5] = [
+        Placement::Fill,
+        Placement::Fit,
+        Placement::Stretch,
+        Placement::Center,
+        Placement::Tile,
+    ];
+
+    /// The name System Settings uses
+    pub fn name(self) -> &'static str {
+        match self {
+            Placement::Fill => "Fill Screen",
+            Placement::Fit => "Fit to Screen",
+            Placement::Stretch => "Stretch to Fill Screen",
+            Placement::Center => "Center",
+            Placement::Tile => "Tile",
+        }
+    }
+
+    /// Value of the placement preference in desktoppicture.db
+    pub fn db_value(self) -> i64 {
+        match self {
+            Placement::Fill => 1,
+            Placement::Fit => 2,
+            Placement::Stretch => 3,
+            Placement::Center => 4,
+            Placement::Tile => 5,
+        }
+    }
+
+    pub fn from_db_value(value: i64) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|placement| placement.db_value() == value)
+    }
+
+    /// Value of `Placement` in com.apple.desktop.plist
+    pub fn plist_value(self) -> &'static str {
+        match self {
+            Placement::Fill => "Crop",
+            Placement::Fit => "SizeToFit",
+            Placement::Stretch => "FillScreen",
+            Placement::Center => "Centered",
+            Placement::Tile => "Tiled",
+        }
+    }
+
+    pub fn from_plist_value(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|placement| placement.plist_value() == value)
+    }
+
+    /// NSImageScaling and whether clipping is allowed. NSWorkspace can't tile
+    fn ns_options(self) -> Option<(i64, bool)> {
+        match self {
+            Placement::Fill => Some((NS_SCALE_PROPORTIONALLY_UP_OR_DOWN, true)),
+            Placement::Fit => Some((NS_SCALE_PROPORTIONALLY_UP_OR_DOWN, false)),
+            Placement::Stretch => Some((NS_SCALE_AXES_INDEPENDENTLY, false)),
+            Placement::Center => Some((NS_SCALE_NONE, false)),
+            Placement::Tile => None,
+        }
+    }
+
+    fn from_ns_options(scaling: i64, clipping: bool) -> Self {
+        match (scaling, clipping) {
+            (NS_SCALE_AXES_INDEPENDENTLY, _) => Placement::Stretch,
+            (NS_SCALE_NONE, _) => Placement::Center,
+            (_, true) => Placement::Fill,
+            (_, false) => Placement::Fit,
+        }
+    }
+
+    /// Whether the Sonoma and later backend can use this placement
+    pub fn supported_on_sonoma_plus(self) -> bool {
+        self.ns_options().is_some()
+    }
+}
+
+pub fn color_to_unit(color: [u8; 3]) -> [f64; 3] {
+    color.map(|c| c as f64 / 255.0)
+}
+
+pub fn color_from_unit(color: [f64; 3]) -> [u8; 3] {
+    color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+/// Reads the options of the main screen through NSWorkspace (AppleScriptObjC)
+pub fn get_placement_sonoma_plus() -> Result<PlacementSettings, String> {
+    let result = applescript::run(
+        "use framework \"AppKit\"
+set ws to current application's NSWorkspace's sharedWorkspace()
+set opts to ws's desktopImageOptionsForScreen:(current application's NSScreen's mainScreen())
+set scaling to opts's objectForKey:(current application's NSWorkspaceDesktopImageScalingKey)
+if scaling is missing value then set scaling to 3
+set clipping to opts's objectForKey:(current application's NSWorkspaceDesktopImageAllowClippingKey)
+if clipping is missing value then set clipping to true
+set fillColor to opts's objectForKey:(current application's NSWorkspaceDesktopImageFillColorKey)
+if fillColor is missing value then return {scaling as integer, clipping as boolean}
+set fillColor to fillColor's colorUsingColorSpace:(current application's NSColorSpace's sRGBColorSpace())
+return {scaling as integer, clipping as boolean, (fillColor's redComponent()) as real, (fillColor's greenComponent()) as real, (fillColor's blueComponent()) as real}",
+    )?;
+
+    let Some(items) = result.as_list() else {
+        return Err(format!("Unexpected desktop image options: {result}"));
+    };
+    let (Some(scaling), Some(clipping)) = (
+        items.first().and_then(Value::as_integer),
+        items.get(1).and_then(Value::as_bool),
+    ) else {
+        return Err(format!("Unexpected desktop image options: {result}"));
+    };
+
+    let mut settings = PlacementSettings {
+        placement: Placement::from_ns_options(scaling, clipping),
+        ..Default::default()
+    };
+    if let [_, _, red, green, blue] = items
+        && let (Some(red), Some(green), Some(blue)) =
+            (red.as_real(), green.as_real(), blue.as_real())
+    {
+        settings.fill_color = color_from_unit([red, green, blue]);
+    }
+    Ok(settings)
+}
+
+/// Sets the options of every screen through NSWorkspace, keeping each screen's image.
+/// Like System Settings, this only changes the current Space
+pub fn set_placement_sonoma_plus(settings: &PlacementSettings) -> Result<(), String> {
+    let Some((scaling, clipping)) = settings.placement.ns_options() else {
+        return Err(format!(
+            "{} is not supported on this version of macOS",
+            settings.placement.name()
+        ));
+    };
+    let [red, green, blue] = color_to_unit(settings.fill_color);
+
+    applescript::run(&format!(
+        "use framework \"AppKit\"
+set ws to current application's NSWorkspace's sharedWorkspace()
+set fillColor to current application's NSColor's colorWithSRGBRed:{} green:{} blue:{} alpha:1.0
+set opts to current application's NSDictionary's dictionaryWithObjects:{{{}, {}, fillColor}} forKeys:{{current application's NSWorkspaceDesktopImageScalingKey, current application's NSWorkspaceDesktopImageAllowClippingKey, current application's NSWorkspaceDesktopImageFillColorKey}}
+repeat with screen in current application's NSScreen's screens()
+    set imageURL to (ws's desktopImageURLForScreen:screen)
+    set {{ok, err}} to (ws's setDesktopImageURL:imageURL forScreen:screen options:opts |error|:(reference))
+    if not ok then error (err's localizedDescription() as text)
+end repeat",
+        Value::Real(red),
+        Value::Real(green),
+        Value::Real(blue),
+        Value::Integer(scaling),
+        Value::Boolean(clipping),
+    ))?;
+    Ok(())
+}
+
+/// Where an image of `image_size` ends up on a screen of `screen`, as one rect per copy
+fn placed_rects(
+    placement: Placement,
+    screen: Rect,
+    image_size: egui::Vec2,
+    scale: f32,
+) -> Vec<Rect> {
+    let screen_size = screen.size();
+    match placement {
+        Placement::Fill | Placement::Fit => {
+            let x = screen_size.x / image_size.x;
+            let y = screen_size.y / image_size.y;
+            let factor = if placement == Placement::Fill {
+                x.max(y)
+            } else {
+                x.min(y)
+            };
+            vec![Rect::from_center_size(screen.center(), image_size * factor)]
+        }
+        Placement::Stretch => vec![screen],
+        Placement::Center => vec![Rect::from_center_size(screen.center(), image_size * scale)],
+        Placement::Tile => {
+            let tile = (image_size * scale).max(vec2(MIN_PREVIEW_TILE, MIN_PREVIEW_TILE));
+            let mut rects = Vec::new();
+            let mut y = screen.top();
+            while y < screen.bottom() {
+                let mut x = screen.left();
+                while x < screen.right() {
+                    rects.push(Rect::from_min_size(pos2(x, y), tile));
+                    x += tile.x;
+                }
+                y += tile.y;
+            }
+            rects
+        }
+    }
+}
+
+/// Draws a small screen showing how the wallpaper will be placed.
+/// `image_size` is the wallpaper's size in pixels, if it could be read
+pub fn show_preview(
+    ui: &mut egui::Ui,
+    wallpaper: &Path,
+    image_size: Option<(u32, u32)>,
+    settings: &PlacementSettings,
+    screen_width: f32,
+) {
+    let (screen, _) = ui.allocate_exact_size(
+        vec2(PREVIEW_WIDTH, PREVIEW_WIDTH / PREVIEW_ASPECT),
+        egui::Sense::hover(),
+    );
+    // How much smaller than the real screen the preview is, for unscaled placements
+    let scale = PREVIEW_WIDTH / screen_width.max(1.0);
+
+    let painter = ui.painter_at(screen);
+    let [red, green, blue] = settings.fill_color;
+    painter.rect_filled(screen, 0.0, Color32::from_rgb(red, green, blue));
+
+    // HEIC and friends can't be measured, so pretend they match the screen
+    let image_size = image_size
+        .filter(|&(width, height)| width > 0 && height > 0)
+        .map(|(width, height)| vec2(width as f32, height as f32))
+        .unwrap_or(vec2(screen_width, screen_width / PREVIEW_ASPECT));
+
+    let image = egui::Image::new(format!("file://{}", wallpaper.display()));
+    let mut clipped = ui.new_child(egui::UiBuilder::new().max_rect(screen));
+    clipped.set_clip_rect(screen);
+    for rect in placed_rects(settings.placement, screen, image_size, scale) {
+        image.clone().paint_at(&clipped, rect);
+    }
+
+    painter.rect_stroke(
+        screen,
+        0.0,
+        egui::Stroke::new(2.0, ui.visuals().strong_text_color()),
+        egui::StrokeKind::Outside,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiny_tiles_are_clamped() {
+        let screen = Rect::from_min_size(pos2(0.0, 0.0), vec2(PREVIEW_WIDTH, 200.0));
+        let rects = placed_rects(Placement::Tile, screen, vec2(1.0, 1.0), 0.1);
+
+        let columns = (PREVIEW_WIDTH / MIN_PREVIEW_TILE).ceil() as usize;
+        let rows = (200.0 / MIN_PREVIEW_TILE).ceil() as usize;
+        assert_eq!(rects.len(), columns * rows);
+    }
+}
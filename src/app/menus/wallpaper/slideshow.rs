@@ -0,0 +1,508 @@
+/*
+    PrefSuite. A Preferences suite for MacOS
+    Copyright (C) 2025-Present Jacob (https://github.com/jacoblightning)
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+// Wallpaper rotation. The GUI only edits the config, the actual switching is done by
+// `PrefSuite --slideshow-daemon`, which is meant to run as a LaunchAgent.
+
+use crate::app::helpers::{app_support_dir, home_dir};
+use crate::command_output;
+use log::{error, info};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const DAEMON_ARG: &str = "--slideshow-daemon";
+const LAUNCH_AGENT_LABEL: &str = "io.github.jacoblightning.prefsuite.slideshow";
+// How often the daemon checks whether it's time to switch
+const TICK: Duration = Duration::from_secs(30);
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum SlideshowMode {
+    #[default]
+    Off,
+    // Rotate through a folder every `interval_minutes`
+    Interval,
+    // Switch to a specific wallpaper at times of day
+    Schedule,
+}
+
+#[derive(Clone)]
+pub struct ScheduleEntry {
+    // Minutes since midnight, local time
+    pub minute_of_day: u32,
+    pub path: String,
+}
+
+#[derive(Clone)]
+pub struct SlideshowConfig {
+    pub mode: SlideshowMode,
+    pub folder: String,
+    pub interval_minutes: u64,
+    pub shuffle: bool,
+    // Kept sorted by time
+    pub schedule: Vec<ScheduleEntry>,
+}
+
+impl Default for SlideshowConfig {
+    fn default() -> Self {
+        Self {
+            mode: SlideshowMode::Off,
+            folder: String::new(),
+            interval_minutes: 30,
+            shuffle: false,
+            schedule: Vec::new(),
+        }
+    }
+}
+
+/// What the daemon remembers between ticks (and restarts)
+#[derive(Default)]
+pub struct SlideshowState {
+    // Unix time of the last switch
+    pub last_change: u64,
+    // Index into the folder of the last wallpaper. None before the first switch
+    pub position: Option<usize>,
+    pub last_path: String,
+}
+
+fn config_path() -> Result<PathBuf, String> {
+    Ok(app_support_dir()?.join("slideshow.json"))
+}
+
+fn state_path() -> Result<PathBuf, String> {
+    Ok(app_support_dir()?.join("slideshow_state.json"))
+}
+
+fn read_json(path: &Path) -> Result<Option<json::JsonValue>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    json::parse(&contents).map(Some).map_err(|e| e.to_string())
+}
+
+fn write_json(path: &Path, value: &json::JsonValue) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(path, value.pretty(4)).map_err(|e| e.to_string())
+}
+
+impl SlideshowConfig {
+    pub fn to_json(&self) -> json::JsonValue {
+        let mut schedule = json::JsonValue::new_array();
+        for entry in &self.schedule {
+            // Can't fail, schedule is an array
+            let _ = schedule.push(json::object! {
+                minute_of_day: entry.minute_of_day,
+                path: entry.path.clone(),
+            });
+        }
+
+        json::object! {
+            mode: match self.mode {
+                SlideshowMode::Off => "off",
+                SlideshowMode::Interval => "interval",
+                SlideshowMode::Schedule => "schedule",
+            },
+            folder: self.folder.clone(),
+            interval_minutes: self.interval_minutes,
+            shuffle: self.shuffle,
+            schedule: schedule,
+        }
+    }
+
+    pub fn from_json(value: &json::JsonValue) -> Self {
+        let default = Self::default();
+
+        let mut schedule: Vec<ScheduleEntry> = value["schedule"]
+            .members()
+            .filter_map(|entry| {
+                Some(ScheduleEntry {
+                    minute_of_day: entry["minute_of_day"].as_u32()?,
+                    path: entry["path"].as_str()?.to_string(),
+                })
+            })
+            .collect();
+        schedule.sort_by_key(|entry| entry.minute_of_day);
+
+        Self {
+            mode: match value["mode"].as_str() {
+                Some("interval") => SlideshowMode::Interval,
+                Some("schedule") => SlideshowMode::Schedule,
+                _ => SlideshowMode::Off,
+            },
+            folder: value["folder"].as_str().unwrap_or_default().to_string(),
+            interval_minutes: value["interval_minutes"]
+                .as_u64()
+                .unwrap_or(default.interval_minutes),
+            shuffle: value["shuffle"].as_bool().unwrap_or(default.shuffle),
+            schedule,
+        }
+    }
+
+    pub fn load() -> Result<Self, String> {
+        Ok(read_json(&config_path()?)?
+            .map(|value| Self::from_json(&value))
+            .unwrap_or_default())
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        write_json(&config_path()?, &self.to_json())
+    }
+}
+
+impl SlideshowState {
+    fn load() -> Result<Self, String> {
+        let Some(value) = read_json(&state_path()?)? else {
+            return Ok(Self::default());
+        };
+
+        Ok(Self {
+            last_change: value["last_change"].as_u64().unwrap_or_default(),
+            position: value["position"].as_usize(),
+            last_path: value["last_path"].as_str().unwrap_or_default().to_string(),
+        })
+    }
+
+    fn save(&self) -> Result<(), String> {
+        write_json(
+            &state_path()?,
+            &json::object! {
+                last_change: self.last_change,
+                position: self.position,
+                last_path: self.last_path.clone(),
+            },
+        )
+    }
+}
+
+/// Parses "HH:MM" into minutes since midnight
+pub fn parse_time_of_day(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.trim().split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+
+    if hours < 24 && minutes < 60 {
+        Some(hours * 60 + minutes)
+    } else {
+        None
+    }
+}
+
+pub fn format_time_of_day(minute_of_day: u32) -> String {
+    format!("{:02}:{:02}", minute_of_day / 60, minute_of_day % 60)
+}
+
+/// The entry in effect at a time of day: the last one at or before it,
+/// wrapping around to yesterday's last entry. `entries` has to be sorted by time
+pub fn entry_at<T>(entries: &[T], minute_of_day: u32, minute: impl Fn(&T) -> u32) -> Option<&T> {
+    entries
+        .iter()
+        .rev()
+        .find(|entry| minute(entry) <= minute_of_day)
+        .or(entries.last())
+}
+
+/// The scheduled wallpaper for a time of day
+pub fn scheduled_wallpaper(
+    schedule: &[ScheduleEntry],
+    minute_of_day: u32,
+) -> Option<&ScheduleEntry> {
+    entry_at(schedule, minute_of_day, |entry| entry.minute_of_day)
+}
+
+/// Picks the next index for interval mode
+fn next_position(state: &SlideshowState, count: usize, shuffle: bool, seed: u64) -> usize {
+    let Some(position) = state.position else {
+        // Start at the first image, or anywhere when shuffling
+        return if shuffle {
+            (seed % count as u64) as usize
+        } else {
+            0
+        };
+    };
+    if count <= 1 {
+        return 0;
+    }
+    if !shuffle {
+        return (position + 1) % count;
+    }
+
+    // xorshift, so we don't need a whole crate to shuffle
+    let mut x = seed | 1;
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+
+    // Never show the same one twice in a row
+    let next = (x % (count as u64 - 1)) as usize;
+    if next >= position { next + 1 } else { next }
+}
+
+/// The local time of day in minutes since midnight
+pub fn local_minute_of_day() -> Result<u32, String> {
+    let time = command_output!("date", "+%H:%M");
+    parse_time_of_day(&time).ok_or_else(|| format!("Unexpected date output: {time:?}"))
+}
+
+/// Decides what (if anything) to switch to now, updating the state
+pub fn tick(
+    config: &SlideshowConfig,
+    state: &mut SlideshowState,
+    now: u64,
+    minute_of_day: u32,
+) -> Result<Option<PathBuf>, String> {
+    match config.mode {
+        SlideshowMode::Off => Ok(None),
+        SlideshowMode::Interval => {
+            if now < state.last_change + config.interval_minutes.max(1) * 60 {
+                return Ok(None);
+            }
+
+            let images = super::images_in(Path::new(&config.folder))?;
+            if images.is_empty() {
+                return Err(format!("No images in {}", config.folder));
+            }
+
+            let position = next_position(state, images.len(), config.shuffle, now);
+            state.position = Some(position);
+            state.last_change = now;
+            state.last_path = images[position].display().to_string();
+            Ok(Some(images[position].clone()))
+        }
+        SlideshowMode::Schedule => {
+            let Some(entry) = scheduled_wallpaper(&config.schedule, minute_of_day) else {
+                return Ok(None);
+            };
+            if entry.path == state.last_path {
+                return Ok(None);
+            }
+
+            state.last_change = now;
+            state.last_path = entry.path.clone();
+            Ok(Some(PathBuf::from(&entry.path)))
+        }
+    }
+}
+
+fn run_tick(state: &mut SlideshowState) -> Result<(), String> {
+    // Reloaded every time so changes from the GUI apply without restarting
+    let config = SlideshowConfig::load()?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs();
+
+    if let Some(path) = tick(&config, state, now, local_minute_of_day()?)? {
+        info!("Slideshow switching to {}", path.display());
+        let path = super::prepare_wallpaper(&path.display().to_string())?;
+        super::change_wallpaper_unattended(&path)?;
+        state.save()?;
+    }
+    Ok(())
+}
+
+/// Entry point for `PrefSuite --slideshow-daemon`. Never returns
+pub fn run_daemon() -> ! {
+    info!("Starting slideshow daemon");
+
+    let mut state = match SlideshowState::load() {
+        Ok(state) => state,
+        Err(e) => {
+            error!("Failed to load slideshow state: {e}");
+            SlideshowState::default()
+        }
+    };
+
+    loop {
+        if let Err(e) = run_tick(&mut state) {
+            error!("Slideshow error: {e}");
+        }
+        std::thread::sleep(TICK);
+    }
+}
+
+fn launch_agent_path() -> Result<PathBuf, String> {
+    Ok(home_dir()?
+        .join("Library/LaunchAgents")
+        .join(format!("{LAUNCH_AGENT_LABEL}.plist")))
+}
+
+pub fn is_launch_agent_installed() -> bool {
+    launch_agent_path().is_ok_and(|path| path.exists())
+}
+
+/// Writes a LaunchAgent running this executable with `DAEMON_ARG` at login, and starts it
+pub fn install_launch_agent() -> Result<(), String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let path = launch_agent_path()?;
+
+    let mut agent = plist::Dictionary::new();
+    agent.insert("Label".into(), LAUNCH_AGENT_LABEL.into());
+    agent.insert(
+        "ProgramArguments".into(),
+        plist::Value::Array(vec![exe.display().to_string().into(), DAEMON_ARG.into()]),
+    );
+    agent.insert("RunAtLoad".into(), true.into());
+    agent.insert("KeepAlive".into(), true.into());
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    plist::Value::Dictionary(agent)
+        .to_file_xml(&path)
+        .map_err(|e| e.to_string())?;
+
+    command_output!("launchctl", "load", &path);
+    Ok(())
+}
+
+pub fn uninstall_launch_agent() -> Result<(), String> {
+    let path = launch_agent_path()?;
+    if !path.exists() {
+        return Ok(());
+    }
+
+    command_output!("launchctl", "unload", &path);
+    std::fs::remove_file(&path).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::desktoppicture::tests::TempDir;
+    use super::*;
+
+    fn state(position: Option<usize>) -> SlideshowState {
+        SlideshowState {
+            position,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn ordered_starts_at_first_image() {
+        assert_eq!(next_position(&state(None), 3, false, 12345), 0);
+        assert_eq!(next_position(&state(Some(0)), 3, false, 12345), 1);
+        assert_eq!(next_position(&state(Some(2)), 3, false, 12345), 0);
+    }
+
+    #[test]
+    fn shuffle_never_repeats() {
+        for seed in 0..100 {
+            assert!(next_position(&state(None), 3, true, seed) < 3);
+            assert_ne!(next_position(&state(Some(1)), 3, true, seed), 1);
+        }
+        assert_eq!(next_position(&state(Some(0)), 1, true, 12345), 0);
+    }
+
+    #[test]
+    fn parses_times_of_day() {
+        assert_eq!(parse_time_of_day("00:00"), Some(0));
+        assert_eq!(parse_time_of_day("07:30"), Some(450));
+        assert_eq!(parse_time_of_day(" 7:05\n"), Some(425));
+        assert_eq!(parse_time_of_day("23:59"), Some(1439));
+        assert_eq!(format_time_of_day(425), "07:05");
+    }
+
+    #[test]
+    fn rejects_invalid_times() {
+        for time in ["24:00", "12:60", "7:5x", "-1:00", "1230", "12:", ""] {
+            assert_eq!(parse_time_of_day(time), None, "{time:?}");
+        }
+    }
+
+    fn schedule() -> Vec<ScheduleEntry> {
+        vec![
+            ScheduleEntry {
+                minute_of_day: 7 * 60,
+                path: String::from("/morning.png"),
+            },
+            ScheduleEntry {
+                minute_of_day: 19 * 60,
+                path: String::from("/evening.png"),
+            },
+        ]
+    }
+
+    #[test]
+    fn schedule_wraps_around_midnight() {
+        let schedule = schedule();
+        let at = |minute| scheduled_wallpaper(&schedule, minute).map(|entry| entry.path.as_str());
+
+        // Before the first entry, yesterday's last one is still in effect
+        assert_eq!(at(0), Some("/evening.png"));
+        assert_eq!(at(7 * 60 - 1), Some("/evening.png"));
+        assert_eq!(at(7 * 60), Some("/morning.png"));
+        assert_eq!(at(12 * 60), Some("/morning.png"));
+        assert_eq!(at(23 * 60 + 59), Some("/evening.png"));
+
+        assert!(scheduled_wallpaper(&[], 0).is_none());
+        assert_eq!(entry_at(&[5, 10], 3, |minute| *minute), Some(&10));
+    }
+
+    #[test]
+    fn schedule_switches_once_per_entry() {
+        let config = SlideshowConfig {
+            mode: SlideshowMode::Schedule,
+            schedule: schedule(),
+            ..Default::default()
+        };
+        let mut state = SlideshowState::default();
+
+        assert_eq!(
+            tick(&config, &mut state, 100, 8 * 60),
+            Ok(Some(PathBuf::from("/morning.png")))
+        );
+        assert_eq!(tick(&config, &mut state, 200, 9 * 60), Ok(None));
+        assert_eq!(
+            tick(&config, &mut state, 300, 20 * 60),
+            Ok(Some(PathBuf::from("/evening.png")))
+        );
+    }
+
+    #[test]
+    fn interval_waits_between_switches() {
+        let dir = TempDir::new("slideshow-interval");
+        for name in ["a.png", "b.jpg", "notes.txt"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        let config = SlideshowConfig {
+            mode: SlideshowMode::Interval,
+            folder: dir.display().to_string(),
+            interval_minutes: 10,
+            ..Default::default()
+        };
+        let mut state = SlideshowState::default();
+        let start = 1_000_000;
+
+        assert_eq!(
+            tick(&config, &mut state, start, 0),
+            Ok(Some(dir.join("a.png")))
+        );
+        assert_eq!(tick(&config, &mut state, start + 10 * 60 - 1, 0), Ok(None));
+        assert_eq!(
+            tick(&config, &mut state, start + 10 * 60, 0),
+            Ok(Some(dir.join("b.jpg")))
+        );
+        assert_eq!(state.last_change, start + 10 * 60);
+
+        // Off never switches
+        let off = SlideshowConfig::default();
+        assert_eq!(tick(&off, &mut state, start + 100 * 60, 0), Ok(None));
+    }
+}
@@ -21,13 +21,17 @@ use crate::app::{Menu, MyApp};
 use crate::{command_output, run_command, command_output_option};
 use std::collections::HashSet;
 use std::path::PathBuf;
-use log::{debug, error, info, trace};
+use std::sync::mpsc::{Receiver, channel};
+use log::{error, info, warn};
 
 use eframe::egui;
 use eframe::egui::RichText;
 
 use std::str::Split;
 
+// What `get_current_wifi_networksetup` reports when there is no network
+const NOT_CONNECTED: &str = "Not connected";
+
 struct WifiInfo {
     // Current Network
     current: Option<String>,
@@ -43,6 +47,12 @@ pub struct WifiData {
     selected_network: String,
     // Password input progress
     password: String,
+    // The network the saved password was looked up for
+    saved_password_ssid: String,
+    // The saved password (or the reason it couldn't be read)
+    saved_password: Option<Result<String, String>>,
+    // A lookup waiting on the keychain prompt
+    saved_password_lookup: Option<Receiver<Result<String, String>>>,
 }
 
 fn is_wifi_on() -> Result<bool, String> {
@@ -98,6 +108,8 @@ fn get_current_wifi_ffi() -> Option<String> {
 
 #[cfg(target_os = "macos")]
 fn get_nearby_wifi_ffi() -> Option<HashSet<String>> {
+    use log::{debug, trace};
+
     let interface = match unsafe { objc2_core_wlan::CWWiFiClient::sharedWiFiClient().interface() } {
         Some(interface) => interface,
         None => {
@@ -201,7 +213,7 @@ fn get_current_wifi_networksetup(os_version: &os_info::Version) -> Option<String
         let network = command_output_option!("networksetup", "-getairportnetwork", "en0");
 
         if network == "You are not associated with an AirPort network.\n" {
-            return Some("Not connected".into());
+            return Some(NOT_CONNECTED.into());
         }
 
         let network = network
@@ -291,6 +303,64 @@ fn join_network(ssid: &str, network_password: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// Reads the saved password of a known network from the System keychain.
+/// This blocks while macOS shows the authorization prompt, see `lookup_saved_password`.
+fn get_saved_password(ssid: &str) -> Result<String, String> {
+    info!("Reading saved password for {ssid} from the System keychain");
+
+    let output = match std::process::Command::new("security")
+        .arg("find-generic-password")
+        .arg("-D")
+        .arg("AirPort network password")
+        .arg("-a")
+        .arg(ssid)
+        .arg("-w")
+        .arg("/Library/Keychains/System.keychain")
+        .output()
+    {
+        Ok(o) => o,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    if output.status.success() {
+        return match String::from_utf8(output.stdout) {
+            Ok(o) => Ok(o.trim_end_matches('\n').to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+    }
+
+    // security exits with the low byte of the OSStatus it got
+    match output.status.code() {
+        // errSecItemNotFound
+        Some(44) => Err(format!("No saved password was found for {ssid}.")),
+        // errSecUserCanceled
+        Some(128) => Err("Authorization was cancelled.".into()),
+        // errSecAuthFailed
+        Some(51) => Err("Authorization failed.".into()),
+        // errSecInteractionNotAllowed
+        Some(36) => Err("The keychain did not allow an authorization prompt.".into()),
+        code => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            warn!("security exited with {code:?}: {stderr}");
+            Err(stderr.trim().to_string())
+        }
+    }
+}
+
+/// Reads the saved password on a background thread so the UI keeps drawing during the prompt
+fn lookup_saved_password(ssid: String, ctx: &egui::Context) -> Receiver<Result<String, String>> {
+    let (sender, receiver) = channel();
+    let ctx = ctx.clone();
+
+    std::thread::spawn(move || {
+        // Nobody is waiting anymore if this fails
+        let _ = sender.send(get_saved_password(&ssid));
+        ctx.request_repaint();
+    });
+
+    receiver
+}
+
 // TODO: Use threads so UI keeps responding
 
 pub fn main(app: &mut MyApp, ctx: &egui::Context) {
@@ -370,6 +440,60 @@ pub fn main(app: &mut MyApp, ctx: &egui::Context) {
             });
             ui.add_space(10.0);
 
+            // Show the password for the picked network, or the current one if none is picked
+            let password_ssid = if app.wifi_data.selected_network.is_empty() {
+                app.wifi_data
+                    .wifi_info
+                    .as_ref()
+                    .unwrap()
+                    .current
+                    .clone()
+                    .filter(|current| current != NOT_CONNECTED)
+            } else {
+                Some(app.wifi_data.selected_network.clone())
+            };
+
+            if let Some(lookup) = app.wifi_data.saved_password_lookup.as_ref()
+                && let Ok(res) = lookup.try_recv()
+            {
+                app.wifi_data.saved_password = Some(res);
+                app.wifi_data.saved_password_lookup = None;
+            }
+
+            if let Some(ssid) = password_ssid {
+                if app.wifi_data.saved_password_ssid != ssid {
+                    // Also forgets a lookup for the old network that is still running
+                    app.wifi_data.saved_password = None;
+                    app.wifi_data.saved_password_lookup = None;
+                }
+
+                if app.wifi_data.saved_password_lookup.is_some() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Waiting for authorization...");
+                    });
+                } else if ui.button(format!("Show saved password for {ssid}")).clicked() {
+                    app.wifi_data.saved_password = None;
+                    app.wifi_data.saved_password_lookup =
+                        Some(lookup_saved_password(ssid.clone(), ctx));
+                    app.wifi_data.saved_password_ssid = ssid;
+                }
+
+                match app.wifi_data.saved_password.as_mut() {
+                    Some(Ok(saved)) => {
+                        ui.horizontal(|ui| {
+                            ui.label("Saved password:");
+                            egui_password::password_ui(ui, saved);
+                        });
+                    }
+                    Some(Err(e)) => {
+                        ui.label(format!("Could not read the saved password: {e}"));
+                    }
+                    None => {}
+                }
+                ui.add_space(10.0);
+            }
+
             //Dropdown of available networks
             match app.wifi_data.wifi_info.as_ref().unwrap().nearby.as_ref() {
                 Some(networks) => {
@@ -27,6 +27,11 @@ use egui_extras::install_image_loaders;
 fn main() -> eframe::Result {
     env_logger::init();
 
+    // Running as the slideshow LaunchAgent, no GUI
+    if std::env::args().any(|arg| arg == app::slideshow::DAEMON_ARG) {
+        app::slideshow::run_daemon();
+    }
+
     trace!("Test trace");
     debug!("Test debug");
     info!( "Test info");